@@ -0,0 +1,75 @@
+use crate::compare::Compare;
+use crate::node::Node;
+use crate::summary::Summary;
+
+use Node::{Leaf2, Leaf3, Nil};
+
+// A subtree under construction, paired with its least key.
+//
+type Level<K, V, S> = (K, Box<Node<K, V, S>>);
+
+// Builds a tree bottom-up from entries in strictly ascending key order,
+// returning its root and the number of entries.  Runs in linear time.
+//
+// Entries are paired off into Leaf3s (with a single Leaf2 at the end if the
+// count is odd), and each level above groups the one below three at a time,
+// falling back to pairs only where needed so that no node is left with a
+// single child.  Every leaf ends up at the same depth.
+//
+pub fn from_sorted<K, V, S, C, I>(entries: I, cmp: &C) -> (Box<Node<K, V, S>>, usize)
+where
+    K: Clone,
+    S: Summary<K>,
+    C: Compare<K>,
+    I: IntoIterator<Item = (K, V)>,
+{
+    let mut level: Vec<Level<K, V, S>> = Vec::new();
+    let mut len = 0;
+    let mut pending: Option<(K, V)> = None;
+    let mut last_key: Option<K> = None;
+
+    for val in entries {
+        if let Some(last_key) = &last_key {
+            assert!(
+                cmp.compare(last_key, &val.0).is_lt(),
+                "from_sorted requires strictly ascending keys"
+            );
+        }
+        last_key = Some(val.0.clone());
+        len += 1;
+        match pending.take() {
+            None => pending = Some(val),
+            Some(val1) => level.push((val1.0.clone(), Box::new(Leaf3 { val1, val2: val }))),
+        }
+    }
+    if let Some(val) = pending {
+        level.push((val.0.clone(), Box::new(Leaf2 { val })));
+    }
+
+    while level.len() > 1 {
+        let mut remaining = level.len();
+        let mut children = level.into_iter();
+        let mut parents = Vec::with_capacity(remaining / 2);
+        while remaining > 0 {
+            let (left_min, left) = children.next().unwrap();
+            let (middle_min, middle) = children.next().unwrap();
+            if remaining == 2 || remaining == 4 {
+                parents.push((left_min, Box::new(Node::inner2(left, middle_min, middle))));
+                remaining -= 2;
+            } else {
+                let (right_min, right) = children.next().unwrap();
+                parents.push((
+                    left_min,
+                    Box::new(Node::inner3(left, middle_min, middle, right_min, right)),
+                ));
+                remaining -= 3;
+            }
+        }
+        level = parents;
+    }
+
+    match level.pop() {
+        Some((_, root)) => (root, len),
+        None => (Box::new(Nil), 0),
+    }
+}
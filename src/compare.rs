@@ -0,0 +1,25 @@
+use std::cmp::Ordering;
+
+/// A total order over `K` used to arrange the keys of a tree.
+///
+/// Implemented for [`Natural`] (the key's own `Ord`) and for any
+/// `Fn(&K, &K) -> Ordering` closure, so an ordering can be picked at runtime.
+pub trait Compare<K> {
+    fn compare(&self, a: &K, b: &K) -> Ordering;
+}
+
+/// Orders keys by their `Ord` implementation.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Natural;
+
+impl<K: Ord> Compare<K> for Natural {
+    fn compare(&self, a: &K, b: &K) -> Ordering {
+        a.cmp(b)
+    }
+}
+
+impl<K, F: Fn(&K, &K) -> Ordering> Compare<K> for F {
+    fn compare(&self, a: &K, b: &K) -> Ordering {
+        self(a, b)
+    }
+}
@@ -1,159 +1,162 @@
+use crate::compare::Compare;
 use crate::node::Node;
+use crate::summary::Summary;
 
-use Node::{Inner2, Inner3, Leaf2, Leaf3, Nil};
+use Node::{Leaf2, Leaf3};
 
-pub enum InsertResult {
+pub enum InsertResult<K, V, S> {
     Ok,
-    Split(i32, Box<Node>),
+    Replaced(V),
+    Split(K, Box<Node<K, V, S>>),
 }
 
-use InsertResult::{Ok, Split};
+use InsertResult::{Ok, Replaced, Split};
 
-pub fn insert_leaf3(new_val: i32, (val1, val2): (i32, i32)) -> (Node, InsertResult) {
-    if new_val < val1 {
-        (
+pub fn insert_leaf3<K: Clone, V, S: Summary<K>, C: Compare<K>>(
+    new_val: (K, V),
+    (mut val1, mut val2): ((K, V), (K, V)),
+    cmp: &C,
+) -> (Node<K, V, S>, InsertResult<K, V, S>) {
+    let order1 = cmp.compare(&new_val.0, &val1.0);
+    let order2 = cmp.compare(&new_val.0, &val2.0);
+    if order1.is_eq() {
+        let old = std::mem::replace(&mut val1.1, new_val.1);
+        (Leaf3 { val1, val2 }, Replaced(old))
+    } else if order2.is_eq() {
+        let old = std::mem::replace(&mut val2.1, new_val.1);
+        (Leaf3 { val1, val2 }, Replaced(old))
+    } else if order1.is_lt() {
+        split_leaf3(0, new_val, (val1, val2))
+    } else if order2.is_lt() {
+        split_leaf3(1, new_val, (val1, val2))
+    } else {
+        split_leaf3(2, new_val, (val1, val2))
+    }
+}
+
+// Splits a full leaf to make room for `new_val`, which belongs at position
+// `slot` among its entries.
+//
+pub fn split_leaf3<K: Clone, V, S: Summary<K>>(
+    slot: usize,
+    new_val: (K, V),
+    (val1, val2): ((K, V), (K, V)),
+) -> (Node<K, V, S>, InsertResult<K, V, S>) {
+    match slot {
+        0 => (
             Leaf2 { val: new_val },
-            Split(val1, Box::new(Leaf3 { val1, val2 })),
-        )
-    } else if new_val < val2 {
-        (
+            Split(val1.0.clone(), Box::new(Leaf3 { val1, val2 })),
+        ),
+        1 => (
             Leaf2 { val: val1 },
             Split(
-                new_val,
+                new_val.0.clone(),
                 Box::new(Leaf3 {
                     val1: new_val,
                     val2,
                 }),
             ),
-        )
-    } else {
-        (
+        ),
+        _ => (
             Leaf2 { val: val1 },
             Split(
-                val2,
+                val2.0.clone(),
                 Box::new(Leaf3 {
                     val1: val2,
                     val2: new_val,
                 }),
             ),
-        )
+        ),
     }
 }
 
-pub fn insert_inner2(
-    new_val: i32,
-    mut left: Box<Node>,
-    right_min: i32,
-    mut right: Box<Node>,
-) -> (Node, InsertResult) {
-    if new_val < right_min {
-        if let Split(split_min, split) = left.insert(new_val) {
-            return (
-                Inner3 {
-                    left,
-                    middle_min: split_min,
-                    middle: split,
-                    right_min,
-                    right,
-                },
-                Ok,
-            );
-        }
+pub fn insert_inner2<K: Clone, V, S: Summary<K>, C: Compare<K>>(
+    new_val: (K, V),
+    mut left: Box<Node<K, V, S>>,
+    right_min: K,
+    mut right: Box<Node<K, V, S>>,
+    cmp: &C,
+) -> (Node<K, V, S>, InsertResult<K, V, S>) {
+    if cmp.compare(&new_val.0, &right_min).is_lt() {
+        let result = left.insert(new_val, cmp);
+        absorb_inner2(0, result, left, right_min, right)
     } else {
-        if let Split(split_min, split) = right.insert(new_val) {
-            return (
-                Inner3 {
-                    left,
-                    middle_min: right_min,
-                    middle: right,
-                    right_min: split_min,
-                    right: split,
-                },
-                Ok,
-            );
-        }
+        let result = right.insert(new_val, cmp);
+        absorb_inner2(1, result, left, right_min, right)
     }
-    return (
-        Inner2 {
-            left,
-            right_min,
-            right,
-        },
-        Ok,
-    );
 }
 
-pub fn insert_inner3(
-    new_val: i32,
-    mut left: Box<Node>,
-    middle_min: i32,
-    mut middle: Box<Node>,
-    right_min: i32,
-    mut right: Box<Node>,
-) -> (Node, InsertResult) {
-    if new_val < middle_min {
-        if let Split(split_min, split) = left.insert(new_val) {
-            return (
-                Inner2 {
-                    left,
-                    right_min: split_min,
-                    right: split,
-                },
-                Split(
-                    middle_min,
-                    Box::new(Inner2 {
-                        left: middle,
-                        right_min,
-                        right,
-                    }),
-                ),
-            );
+// Rebuilds an Inner2 once an insert into its child `index` has returned
+// `result`, taking in the new sibling if the child split.
+//
+pub fn absorb_inner2<K, V, S: Summary<K>>(
+    index: usize,
+    result: InsertResult<K, V, S>,
+    left: Box<Node<K, V, S>>,
+    right_min: K,
+    right: Box<Node<K, V, S>>,
+) -> (Node<K, V, S>, InsertResult<K, V, S>) {
+    match (index, result) {
+        (0, Split(split_min, split)) => {
+            (Node::inner3(left, split_min, split, right_min, right), Ok)
         }
-    } else if new_val < right_min {
-        if let Split(split_min, split) = middle.insert(new_val) {
-            return (
-                Inner2 {
-                    left,
-                    right_min: middle_min,
-                    right: middle,
-                },
-                Split(
-                    split_min,
-                    Box::new(Inner2 {
-                        left: split,
-                        right_min,
-                        right,
-                    }),
-                ),
-            );
+        (_, Split(split_min, split)) => {
+            (Node::inner3(left, right_min, right, split_min, split), Ok)
         }
+        (_, result) => (Node::inner2(left, right_min, right), result),
+    }
+}
+
+pub fn insert_inner3<K: Clone, V, S: Summary<K>, C: Compare<K>>(
+    new_val: (K, V),
+    mut left: Box<Node<K, V, S>>,
+    middle_min: K,
+    mut middle: Box<Node<K, V, S>>,
+    right_min: K,
+    mut right: Box<Node<K, V, S>>,
+    cmp: &C,
+) -> (Node<K, V, S>, InsertResult<K, V, S>) {
+    if cmp.compare(&new_val.0, &middle_min).is_lt() {
+        let result = left.insert(new_val, cmp);
+        absorb_inner3(0, result, left, middle_min, middle, right_min, right)
+    } else if cmp.compare(&new_val.0, &right_min).is_lt() {
+        let result = middle.insert(new_val, cmp);
+        absorb_inner3(1, result, left, middle_min, middle, right_min, right)
     } else {
-        if let Split(split_min, split) = right.insert(new_val) {
-            return (
-                Inner2 {
-                    left,
-                    right_min: middle_min,
-                    right: middle,
-                },
-                Split(
-                    right_min,
-                    Box::new(Inner2 {
-                        left: right,
-                        right_min: split_min,
-                        right: split,
-                    }),
-                ),
-            );
-        }
+        let result = right.insert(new_val, cmp);
+        absorb_inner3(2, result, left, middle_min, middle, right_min, right)
+    }
+}
+
+// Rebuilds an Inner3 once an insert into its child `index` has returned
+// `result`.  If the child split, the four resulting children are shared out
+// two apiece between this node and a new split-off sibling.
+//
+pub fn absorb_inner3<K, V, S: Summary<K>>(
+    index: usize,
+    result: InsertResult<K, V, S>,
+    left: Box<Node<K, V, S>>,
+    middle_min: K,
+    middle: Box<Node<K, V, S>>,
+    right_min: K,
+    right: Box<Node<K, V, S>>,
+) -> (Node<K, V, S>, InsertResult<K, V, S>) {
+    match (index, result) {
+        (0, Split(split_min, split)) => (
+            Node::inner2(left, split_min, split),
+            Split(middle_min, Box::new(Node::inner2(middle, right_min, right))),
+        ),
+        (1, Split(split_min, split)) => (
+            Node::inner2(left, middle_min, middle),
+            Split(split_min, Box::new(Node::inner2(split, right_min, right))),
+        ),
+        (_, Split(split_min, split)) => (
+            Node::inner2(left, middle_min, middle),
+            Split(right_min, Box::new(Node::inner2(right, split_min, split))),
+        ),
+        (_, result) => (
+            Node::inner3(left, middle_min, middle, right_min, right),
+            result,
+        ),
     }
-    return (
-        Inner3 {
-            left,
-            middle_min,
-            middle,
-            right_min,
-            right,
-        },
-        Ok,
-    );
 }
@@ -0,0 +1,369 @@
+use std::cmp::Ordering;
+use std::iter::{FromIterator, FusedIterator};
+
+use gcollections::ops::{Bounded, IsEmpty, Singleton};
+use interval::Interval;
+
+use crate::compare::Compare;
+use crate::iter::Pending;
+use crate::map::{self, TreeMap};
+use crate::node::Node;
+use crate::summary::Summary;
+
+use Node::{Inner2, Inner3, Leaf2, Leaf3, Nil};
+
+/// A set of closed intervals that can be searched for the intervals
+/// overlapping a query, backed by a 2-3 tree.
+///
+/// Intervals are ordered by lower bound, then by upper bound, and every
+/// inner node caches the greatest upper bound beneath it, so that searches
+/// skip subtrees that end before the query starts.
+///
+/// ```
+/// use interval::ops::Range;
+/// use interval::Interval;
+/// use two3tree::IntervalTree;
+///
+/// let mut bookings = IntervalTree::new();
+/// bookings.insert(Interval::new(9, 12));
+/// bookings.insert(Interval::new(11, 14));
+/// bookings.insert(Interval::new(15, 17));
+///
+/// let clashes: Vec<_> = bookings.overlapping(&Interval::new(12, 15)).collect();
+/// assert_eq!(clashes.len(), 3);
+/// assert_eq!(bookings.stabbing(10).count(), 1);
+/// ```
+#[derive(Clone, Debug)]
+pub struct IntervalTree {
+    intervals: TreeMap<Interval<i32>, (), ByLower, MaxUpper>,
+}
+
+// Orders intervals by lower bound, breaking ties by upper bound so that
+// distinct intervals starting together can be stored side by side.
+//
+#[derive(Clone, Copy, Debug, Default)]
+struct ByLower;
+
+impl Compare<Interval<i32>> for ByLower {
+    fn compare(&self, a: &Interval<i32>, b: &Interval<i32>) -> Ordering {
+        (a.lower(), a.upper()).cmp(&(b.lower(), b.upper()))
+    }
+}
+
+// The greatest upper bound of a run of intervals; `None` for an empty run.
+//
+#[derive(Clone, Debug)]
+struct MaxUpper(Option<i32>);
+
+impl Summary<Interval<i32>> for MaxUpper {
+    fn empty() -> Self {
+        MaxUpper(None)
+    }
+    fn of(key: &Interval<i32>) -> Self {
+        MaxUpper(Some(key.upper()))
+    }
+    fn combine(&self, other: &Self) -> Self {
+        MaxUpper(self.0.max(other.0))
+    }
+}
+
+impl IntervalTree {
+    /// Creates an empty interval tree.
+    pub fn new() -> Self {
+        Self {
+            intervals: TreeMap::augmented(ByLower),
+        }
+    }
+
+    /// Adds `interval` to the tree.
+    ///
+    /// Returns false, leaving the tree unchanged, if the interval is empty or
+    /// an interval with the same bounds is already present.
+    pub fn insert(&mut self, interval: Interval<i32>) -> bool {
+        !interval.is_empty() && self.intervals.insert(interval, ()).is_none()
+    }
+
+    /// Removes the interval with the same bounds as `interval`; returns false
+    /// if there was none.
+    pub fn remove(&mut self, interval: &Interval<i32>) -> bool {
+        !interval.is_empty() && self.intervals.remove(interval).is_some()
+    }
+
+    /// Returns true if an interval with the same bounds as `interval` is
+    /// present.
+    pub fn contains(&self, interval: &Interval<i32>) -> bool {
+        !interval.is_empty() && self.intervals.contains_key(interval)
+    }
+
+    /// Returns the number of intervals in the tree.
+    pub fn len(&self) -> usize {
+        self.intervals.len()
+    }
+
+    /// Returns true if the tree holds no intervals.
+    pub fn is_empty(&self) -> bool {
+        self.intervals.is_empty()
+    }
+
+    /// Returns an iterator over the intervals in order of lower bound.
+    pub fn iter(&self) -> Iter<'_> {
+        Iter {
+            inner: self.intervals.iter(),
+        }
+    }
+
+    /// Returns an iterator over the intervals that share at least one point
+    /// with `query`, in order of lower bound.
+    ///
+    /// Subtrees whose intervals all end before `query` starts, or all start
+    /// after it ends, are skipped whole, so only the paths down to matching
+    /// intervals and the path along the end of `query` are walked: O(log n +
+    /// k) for k matches that sit together in order, and never more than
+    /// O(log n) per match.
+    pub fn overlapping(&self, query: &Interval<i32>) -> Overlapping<'_> {
+        let mut overlapping = Overlapping {
+            pending: Vec::with_capacity(2 * self.intervals.height()),
+            lower: 0,
+            upper: 0,
+        };
+        if !query.is_empty() {
+            overlapping.lower = query.lower();
+            overlapping.upper = query.upper();
+            overlapping
+                .pending
+                .push(Pending::Node(self.intervals.root()));
+        }
+        overlapping
+    }
+
+    /// Returns an iterator over the intervals that contain `point`, in order
+    /// of lower bound; see `overlapping`.
+    pub fn stabbing(&self, point: i32) -> Overlapping<'_> {
+        self.overlapping(&Interval::singleton(point))
+    }
+
+    #[cfg(test)]
+    fn check(&self) {
+        self.intervals.check();
+    }
+}
+
+impl Default for IntervalTree {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl FromIterator<Interval<i32>> for IntervalTree {
+    fn from_iter<I: IntoIterator<Item = Interval<i32>>>(iter: I) -> Self {
+        Self {
+            intervals: iter
+                .into_iter()
+                .filter(|interval| !interval.is_empty())
+                .map(|interval| (interval, ()))
+                .collect(),
+        }
+    }
+}
+
+impl<'a> IntoIterator for &'a IntervalTree {
+    type Item = &'a Interval<i32>;
+    type IntoIter = Iter<'a>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+/// An iterator over the intervals of an `IntervalTree`, in order of lower
+/// bound.
+pub struct Iter<'a> {
+    inner: map::Iter<'a, Interval<i32>, (), MaxUpper>,
+}
+
+impl<'a> Iterator for Iter<'a> {
+    type Item = &'a Interval<i32>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.inner.next().map(|(interval, _)| interval)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.inner.size_hint()
+    }
+}
+
+impl DoubleEndedIterator for Iter<'_> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        self.inner.next_back().map(|(interval, _)| interval)
+    }
+}
+
+impl ExactSizeIterator for Iter<'_> {}
+
+impl FusedIterator for Iter<'_> {}
+
+/// An iterator over the intervals of an `IntervalTree` that overlap a query,
+/// returned by `IntervalTree::overlapping` and `IntervalTree::stabbing`.
+pub struct Overlapping<'a> {
+    pending: Vec<Pending<'a, Interval<i32>, (), MaxUpper>>,
+    lower: i32,
+    upper: i32,
+}
+
+impl<'a> Overlapping<'a> {
+    fn push_entry(&mut self, entry: &'a (Interval<i32>, ())) {
+        if entry.0.lower() <= self.upper && entry.0.upper() >= self.lower {
+            self.pending.push(Pending::Entry(entry));
+        }
+    }
+
+    // Every interval in a child starts at or after its separator, so a child
+    // whose separator starts after the query ends can be skipped.
+    //
+    fn push_child(&mut self, child: &'a Node<Interval<i32>, (), MaxUpper>, min: &Interval<i32>) {
+        if min.lower() <= self.upper {
+            self.pending.push(Pending::Node(child));
+        }
+    }
+}
+
+impl<'a> Iterator for Overlapping<'a> {
+    type Item = &'a Interval<i32>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while let Some(pending) = self.pending.pop() {
+            let node = match pending {
+                Pending::Entry((interval, _)) => return Some(interval),
+                Pending::Node(node) => node,
+            };
+            // Nothing beneath ends late enough to reach the query.
+            if node.summary().0.is_none_or(|max| max < self.lower) {
+                continue;
+            }
+            match node {
+                Nil => {}
+                Leaf2 { val } => self.push_entry(val),
+                Leaf3 { val1, val2 } => {
+                    self.push_entry(val2);
+                    self.push_entry(val1);
+                }
+                Inner2 {
+                    left,
+                    right_min,
+                    right,
+                    ..
+                } => {
+                    self.push_child(right, right_min);
+                    self.pending.push(Pending::Node(left));
+                }
+                Inner3 {
+                    left,
+                    middle_min,
+                    middle,
+                    right_min,
+                    right,
+                    ..
+                } => {
+                    self.push_child(right, right_min);
+                    self.push_child(middle, middle_min);
+                    self.pending.push(Pending::Node(left));
+                }
+            }
+        }
+        None
+    }
+}
+
+impl FusedIterator for Overlapping<'_> {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use gcollections::ops::Empty;
+    use interval::ops::Range;
+
+    fn brute_force(intervals: &[Interval<i32>], query: &Interval<i32>) -> Vec<Interval<i32>> {
+        let mut expected: Vec<_> = intervals
+            .iter()
+            .filter(|i| i.lower() <= query.upper() && i.upper() >= query.lower())
+            .copied()
+            .collect();
+        expected.sort_by(|a, b| ByLower.compare(a, b));
+        expected
+    }
+
+    #[test]
+    fn overlapping_test() {
+        let mut t = IntervalTree::new();
+        let mut intervals = Vec::new();
+
+        for k in 0..2000 {
+            let lower = (k * 7919) % 5000;
+            let interval = Interval::new(lower, lower + (k * 31) % 97);
+            if t.insert(interval) {
+                intervals.push(interval);
+            }
+        }
+
+        assert!(!t.insert(intervals[0]));
+        assert!(!t.insert(Interval::empty()));
+        assert_eq!(t.len(), intervals.len());
+        t.check();
+
+        let queries = |intervals: &[Interval<i32>], t: &IntervalTree| {
+            for lower in (-100..5200).step_by(37) {
+                for len in [0, 1, 10, 250] {
+                    let query = Interval::new(lower, lower + len);
+                    let found: Vec<_> = t.overlapping(&query).copied().collect();
+                    assert_eq!(found, brute_force(intervals, &query), "query={}", query);
+                }
+                let found: Vec<_> = t.stabbing(lower).copied().collect();
+                let expected = brute_force(intervals, &Interval::singleton(lower));
+                assert_eq!(found, expected, "point={}", lower);
+            }
+            assert_eq!(t.overlapping(&Interval::empty()).next(), None);
+        };
+
+        queries(&intervals, &t);
+
+        // Removals must keep the cached upper bounds in step.
+        for interval in intervals.iter().step_by(3) {
+            assert!(t.remove(interval));
+            assert!(!t.contains(interval));
+        }
+        let intervals: Vec<_> = intervals
+            .into_iter()
+            .enumerate()
+            .filter(|(i, _)| i % 3 != 0)
+            .map(|(_, interval)| interval)
+            .collect();
+        t.check();
+
+        queries(&intervals, &t);
+
+        let rebuilt: IntervalTree = intervals.iter().copied().collect();
+        assert!(rebuilt.iter().eq(t.iter()));
+        queries(&intervals, &rebuilt);
+    }
+
+    #[test]
+    fn long_interval_test() {
+        // One long interval early in the order must be found from far to
+        // its right, which only the cached upper bounds make possible.
+        let mut t = IntervalTree::new();
+        for k in 0..1000 {
+            t.insert(Interval::new(k * 10, k * 10 + 5));
+        }
+        t.insert(Interval::new(3, 9000));
+
+        assert!(t.stabbing(8).eq([Interval::new(3, 9000)].iter()));
+        assert!(t
+            .stabbing(5002)
+            .eq([Interval::new(3, 9000), Interval::new(5000, 5005)].iter()));
+        assert_eq!(t.stabbing(9007).count(), 0);
+        assert_eq!(t.stabbing(-1).count(), 0);
+        assert_eq!(t.overlapping(&Interval::new(8, 9)).count(), 1);
+        assert_eq!(t.overlapping(&Interval::new(-50, 100_000)).count(), 1001);
+    }
+}
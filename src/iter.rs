@@ -0,0 +1,462 @@
+use std::collections::VecDeque;
+use std::ops::Bound::{self, Excluded, Included, Unbounded};
+
+use crate::compare::Compare;
+use crate::node::Node;
+
+use Node::{Inner2, Inner3, Leaf2, Leaf3, Nil};
+
+// Work left on one side of a traversal: either a whole subtree or a single
+// entry from a leaf that has already been opened.
+//
+pub enum Pending<'a, K, V, S> {
+    Node(&'a Node<K, V, S>),
+    Entry(&'a (K, V)),
+}
+
+// In-order walk over the entries of a tree, driven by an explicit stack at
+// each end.  The two stacks never look at each other; the caller must stop
+// once the ends meet (see `Entries`).
+//
+pub struct Walk<'a, K, V, S> {
+    front: Vec<Pending<'a, K, V, S>>,
+    back: Vec<Pending<'a, K, V, S>>,
+}
+
+impl<'a, K, V, S> Walk<'a, K, V, S> {
+    pub fn new(root: &'a Node<K, V, S>) -> Self {
+        Self {
+            front: vec![Pending::Node(root)],
+            back: vec![Pending::Node(root)],
+        }
+    }
+
+    // Positions the front of the walk on the first entry at or after `lower`
+    // and the back on the last entry at or before `upper`, descending
+    // straight to each bound by way of the separator keys.
+    //
+    pub fn range<C: Compare<K>>(
+        root: &'a Node<K, V, S>,
+        lower: Bound<&K>,
+        upper: Bound<&K>,
+        cmp: &C,
+    ) -> Self {
+        let mut walk = Self {
+            front: Vec::new(),
+            back: Vec::new(),
+        };
+        walk.seek_front(root, lower, cmp);
+        walk.seek_back(root, upper, cmp);
+        walk
+    }
+
+    fn seek_front<C: Compare<K>>(
+        &mut self,
+        mut node: &'a Node<K, V, S>,
+        lower: Bound<&K>,
+        cmp: &C,
+    ) {
+        // Whether the first entry past `lower` may lie to the left of `sep`.
+        let left_of = |sep: &K| match lower {
+            Unbounded => true,
+            Included(key) | Excluded(key) => cmp.compare(key, sep).is_lt(),
+        };
+        let in_range = |key: &K| match lower {
+            Unbounded => true,
+            Included(bound) => cmp.compare(key, bound).is_ge(),
+            Excluded(bound) => cmp.compare(key, bound).is_gt(),
+        };
+        loop {
+            match node {
+                Nil => return,
+                Leaf2 { val } => {
+                    if in_range(&val.0) {
+                        self.front.push(Pending::Entry(val));
+                    }
+                    return;
+                }
+                Leaf3 { val1, val2 } => {
+                    if in_range(&val2.0) {
+                        self.front.push(Pending::Entry(val2));
+                    }
+                    if in_range(&val1.0) {
+                        self.front.push(Pending::Entry(val1));
+                    }
+                    return;
+                }
+                Inner2 {
+                    left,
+                    right_min,
+                    right,
+                    ..
+                } => {
+                    if left_of(right_min) {
+                        self.front.push(Pending::Node(right));
+                        node = left;
+                    } else {
+                        node = right;
+                    }
+                }
+                Inner3 {
+                    left,
+                    middle_min,
+                    middle,
+                    right_min,
+                    right,
+                    ..
+                } => {
+                    if left_of(middle_min) {
+                        self.front.push(Pending::Node(right));
+                        self.front.push(Pending::Node(middle));
+                        node = left;
+                    } else if left_of(right_min) {
+                        self.front.push(Pending::Node(right));
+                        node = middle;
+                    } else {
+                        node = right;
+                    }
+                }
+            }
+        }
+    }
+
+    fn seek_back<C: Compare<K>>(&mut self, mut node: &'a Node<K, V, S>, upper: Bound<&K>, cmp: &C) {
+        // Whether the last entry before `upper` may lie at or right of `sep`.
+        let right_of = |sep: &K| match upper {
+            Unbounded => true,
+            Included(key) => cmp.compare(key, sep).is_ge(),
+            Excluded(key) => cmp.compare(key, sep).is_gt(),
+        };
+        let in_range = |key: &K| match upper {
+            Unbounded => true,
+            Included(bound) => cmp.compare(key, bound).is_le(),
+            Excluded(bound) => cmp.compare(key, bound).is_lt(),
+        };
+        loop {
+            match node {
+                Nil => return,
+                Leaf2 { val } => {
+                    if in_range(&val.0) {
+                        self.back.push(Pending::Entry(val));
+                    }
+                    return;
+                }
+                Leaf3 { val1, val2 } => {
+                    if in_range(&val1.0) {
+                        self.back.push(Pending::Entry(val1));
+                    }
+                    if in_range(&val2.0) {
+                        self.back.push(Pending::Entry(val2));
+                    }
+                    return;
+                }
+                Inner2 {
+                    left,
+                    right_min,
+                    right,
+                    ..
+                } => {
+                    if right_of(right_min) {
+                        self.back.push(Pending::Node(left));
+                        node = right;
+                    } else {
+                        node = left;
+                    }
+                }
+                Inner3 {
+                    left,
+                    middle_min,
+                    middle,
+                    right_min,
+                    right,
+                    ..
+                } => {
+                    if right_of(right_min) {
+                        self.back.push(Pending::Node(left));
+                        self.back.push(Pending::Node(middle));
+                        node = right;
+                    } else if right_of(middle_min) {
+                        self.back.push(Pending::Node(left));
+                        node = middle;
+                    } else {
+                        node = left;
+                    }
+                }
+            }
+        }
+    }
+
+    pub fn next_front(&mut self) -> Option<&'a (K, V)> {
+        while let Some(pending) = self.front.pop() {
+            match pending {
+                Pending::Entry(val) => return Some(val),
+                Pending::Node(Nil) => {}
+                Pending::Node(Leaf2 { val }) => return Some(val),
+                Pending::Node(Leaf3 { val1, val2 }) => {
+                    self.front.push(Pending::Entry(val2));
+                    return Some(val1);
+                }
+                Pending::Node(Inner2 { left, right, .. }) => {
+                    self.front.push(Pending::Node(right));
+                    self.front.push(Pending::Node(left));
+                }
+                Pending::Node(Inner3 {
+                    left,
+                    middle,
+                    right,
+                    ..
+                }) => {
+                    self.front.push(Pending::Node(right));
+                    self.front.push(Pending::Node(middle));
+                    self.front.push(Pending::Node(left));
+                }
+            }
+        }
+        None
+    }
+
+    pub fn next_back(&mut self) -> Option<&'a (K, V)> {
+        while let Some(pending) = self.back.pop() {
+            match pending {
+                Pending::Entry(val) => return Some(val),
+                Pending::Node(Nil) => {}
+                Pending::Node(Leaf2 { val }) => return Some(val),
+                Pending::Node(Leaf3 { val1, val2 }) => {
+                    self.back.push(Pending::Entry(val1));
+                    return Some(val2);
+                }
+                Pending::Node(Inner2 { left, right, .. }) => {
+                    self.back.push(Pending::Node(left));
+                    self.back.push(Pending::Node(right));
+                }
+                Pending::Node(Inner3 {
+                    left,
+                    middle,
+                    right,
+                    ..
+                }) => {
+                    self.back.push(Pending::Node(left));
+                    self.back.push(Pending::Node(middle));
+                    self.back.push(Pending::Node(right));
+                }
+            }
+        }
+        None
+    }
+}
+
+// A `Walk` over a whole tree, bounded by the number of entries it holds.
+//
+pub struct Entries<'a, K, V, S> {
+    walk: Walk<'a, K, V, S>,
+    len: usize,
+}
+
+impl<'a, K, V, S> Entries<'a, K, V, S> {
+    pub fn new(root: &'a Node<K, V, S>, len: usize) -> Self {
+        Self {
+            walk: Walk::new(root),
+            len,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn next_front(&mut self) -> Option<&'a (K, V)> {
+        if self.len == 0 {
+            return None;
+        }
+        self.len -= 1;
+        self.walk.next_front()
+    }
+
+    pub fn next_back(&mut self) -> Option<&'a (K, V)> {
+        if self.len == 0 {
+            return None;
+        }
+        self.len -= 1;
+        self.walk.next_back()
+    }
+}
+
+// Panics if the bounds describe a backwards or otherwise malformed range.
+//
+pub fn check_range<K, C: Compare<K>>(lower: Bound<&K>, upper: Bound<&K>, cmp: &C) {
+    match (lower, upper) {
+        (Excluded(start), Excluded(end)) if cmp.compare(start, end).is_eq() => {
+            panic!("range start and end are equal and excluded")
+        }
+        (Included(start) | Excluded(start), Included(end) | Excluded(end))
+            if cmp.compare(start, end).is_gt() =>
+        {
+            panic!("range start is greater than range end")
+        }
+        _ => {}
+    }
+}
+
+// A `Walk` between two bounds.  The first and last entries still to be
+// yielded are kept one step ahead of the walk, so the ends meet when they
+// land on the same entry and no keys are compared after construction.
+//
+pub struct RangeEntries<'a, K, V, S> {
+    walk: Walk<'a, K, V, S>,
+    first: Option<&'a (K, V)>,
+    last: Option<&'a (K, V)>,
+}
+
+impl<'a, K, V, S> RangeEntries<'a, K, V, S> {
+    pub fn new<C: Compare<K>>(
+        root: &'a Node<K, V, S>,
+        lower: Bound<&K>,
+        upper: Bound<&K>,
+        cmp: &C,
+    ) -> Self {
+        check_range(lower, upper, cmp);
+        let mut walk = Walk::range(root, lower, upper, cmp);
+        let first = walk.next_front();
+        let last = walk.next_back();
+        match (first, last) {
+            (Some(first), Some(last)) if cmp.compare(&first.0, &last.0).is_le() => Self {
+                walk,
+                first: Some(first),
+                last: Some(last),
+            },
+            _ => Self {
+                walk,
+                first: None,
+                last: None,
+            },
+        }
+    }
+
+    pub fn next_front(&mut self) -> Option<&'a (K, V)> {
+        let first = self.first?;
+        if std::ptr::eq(first, self.last?) {
+            self.first = None;
+            self.last = None;
+        } else {
+            self.first = self.walk.next_front();
+        }
+        Some(first)
+    }
+
+    pub fn next_back(&mut self) -> Option<&'a (K, V)> {
+        let last = self.last?;
+        if std::ptr::eq(self.first?, last) {
+            self.first = None;
+            self.last = None;
+        } else {
+            self.last = self.walk.next_back();
+        }
+        Some(last)
+    }
+}
+
+enum Owned<K, V, S> {
+    Node(Box<Node<K, V, S>>),
+    Entry((K, V)),
+}
+
+// Consuming in-order walk.  Both ends work on the same deque, expanding inner
+// nodes in place, so the tree is taken apart (and its boxes freed) without
+// recursion and without ever rebalancing.  The deque holds at most a few
+// entries per level of the tree.
+//
+pub struct IntoEntries<K, V, S> {
+    pending: VecDeque<Owned<K, V, S>>,
+    len: usize,
+}
+
+impl<K, V, S> IntoEntries<K, V, S> {
+    pub fn new(root: Box<Node<K, V, S>>, len: usize) -> Self {
+        Self {
+            pending: VecDeque::from(vec![Owned::Node(root)]),
+            len,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn next_front(&mut self) -> Option<(K, V)> {
+        while let Some(pending) = self.pending.pop_front() {
+            let node = match pending {
+                Owned::Entry(val) => {
+                    self.len -= 1;
+                    return Some(val);
+                }
+                Owned::Node(node) => *node,
+            };
+            match node {
+                Nil => {}
+                Leaf2 { val } => {
+                    self.len -= 1;
+                    return Some(val);
+                }
+                Leaf3 { val1, val2 } => {
+                    self.pending.push_front(Owned::Entry(val2));
+                    self.len -= 1;
+                    return Some(val1);
+                }
+                Inner2 { left, right, .. } => {
+                    self.pending.push_front(Owned::Node(right));
+                    self.pending.push_front(Owned::Node(left));
+                }
+                Inner3 {
+                    left,
+                    middle,
+                    right,
+                    ..
+                } => {
+                    self.pending.push_front(Owned::Node(right));
+                    self.pending.push_front(Owned::Node(middle));
+                    self.pending.push_front(Owned::Node(left));
+                }
+            }
+        }
+        None
+    }
+
+    pub fn next_back(&mut self) -> Option<(K, V)> {
+        while let Some(pending) = self.pending.pop_back() {
+            let node = match pending {
+                Owned::Entry(val) => {
+                    self.len -= 1;
+                    return Some(val);
+                }
+                Owned::Node(node) => *node,
+            };
+            match node {
+                Nil => {}
+                Leaf2 { val } => {
+                    self.len -= 1;
+                    return Some(val);
+                }
+                Leaf3 { val1, val2 } => {
+                    self.pending.push_back(Owned::Entry(val1));
+                    self.len -= 1;
+                    return Some(val2);
+                }
+                Inner2 { left, right, .. } => {
+                    self.pending.push_back(Owned::Node(left));
+                    self.pending.push_back(Owned::Node(right));
+                }
+                Inner3 {
+                    left,
+                    middle,
+                    right,
+                    ..
+                } => {
+                    self.pending.push_back(Owned::Node(left));
+                    self.pending.push_back(Owned::Node(middle));
+                    self.pending.push_back(Owned::Node(right));
+                }
+            }
+        }
+        None
+    }
+}
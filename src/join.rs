@@ -0,0 +1,270 @@
+use std::ops::Bound::{self, Excluded, Included, Unbounded};
+
+use crate::compare::Compare;
+use crate::insert::{absorb_inner2, absorb_inner3, InsertResult};
+use crate::node::Node;
+use crate::summary::Summary;
+
+use Node::{Inner2, Inner3, Leaf2, Leaf3, Nil};
+
+// A subtree paired with its height, so that joins can find the level at which
+// to graft without walking down to a leaf each time.
+//
+pub struct Part<K, V, S> {
+    pub root: Box<Node<K, V, S>>,
+    pub height: usize,
+}
+
+impl<K, V, S> Part<K, V, S> {
+    pub fn empty() -> Self {
+        Self {
+            root: Box::new(Nil),
+            height: 0,
+        }
+    }
+
+    pub fn new(root: Box<Node<K, V, S>>, height: usize) -> Self {
+        Self { root, height }
+    }
+}
+
+// Joins two trees into one, given that every key in `left` is less than `sep`
+// and `sep` is no greater than any key in `right`.  The shorter tree is
+// grafted onto the facing spine of the taller one at the level where the
+// heights match, so the cost is proportional to the difference in heights.
+//
+pub fn join<K: Clone, V, S: Summary<K>>(
+    mut left: Part<K, V, S>,
+    sep: K,
+    mut right: Part<K, V, S>,
+) -> Part<K, V, S> {
+    use InsertResult::Split;
+    if left.height == 0 {
+        return right;
+    }
+    if right.height == 0 {
+        return left;
+    }
+    if left.height == right.height {
+        return Part::new(
+            Box::new(Node::inner2(left.root, sep, right.root)),
+            left.height + 1,
+        );
+    }
+    if left.height > right.height {
+        match graft_right(&mut left.root, left.height, sep, right) {
+            Split(split_min, split) => Part::new(
+                Box::new(Node::inner2(left.root, split_min, split)),
+                left.height + 1,
+            ),
+            _ => left,
+        }
+    } else {
+        match graft_left(&mut right.root, right.height, left, sep) {
+            Split(split_min, split) => Part::new(
+                Box::new(Node::inner2(right.root, split_min, split)),
+                right.height + 1,
+            ),
+            _ => right,
+        }
+    }
+}
+
+// Joins two trees where every key in `left` is less than every key in
+// `right`, using the least key of `right` as the separator.
+//
+pub fn concat<K: Clone, V, S: Summary<K>>(
+    left: Part<K, V, S>,
+    right: Part<K, V, S>,
+) -> Part<K, V, S> {
+    match right.root.first() {
+        Some((sep, _)) => {
+            let sep = sep.clone();
+            join(left, sep, right)
+        }
+        None => left,
+    }
+}
+
+// Appends `subtree` as the new rightmost descendant of `node` at the level
+// just above the subtree's own height, passing any split back up.
+//
+fn graft_right<K: Clone, V, S: Summary<K>>(
+    node: &mut Node<K, V, S>,
+    height: usize,
+    sep: K,
+    subtree: Part<K, V, S>,
+) -> InsertResult<K, V, S> {
+    use InsertResult::Split;
+    if height == subtree.height + 1 {
+        return node.merge_right(sep, subtree.root);
+    }
+    let result = match node {
+        Inner2 { right, .. } | Inner3 { right, .. } => graft_right(right, height - 1, sep, subtree),
+        _ => panic!("graft_right must stop at an inner node"),
+    };
+    match result {
+        Split(split_min, split) => node.merge_right(split_min, split),
+        result => {
+            node.update_cache();
+            result
+        }
+    }
+}
+
+// Prepends `subtree` as the new leftmost descendant of `node` at the level
+// just above the subtree's own height, passing any split back up.
+//
+fn graft_left<K: Clone, V, S: Summary<K>>(
+    node: &mut Node<K, V, S>,
+    height: usize,
+    subtree: Part<K, V, S>,
+    sep: K,
+) -> InsertResult<K, V, S> {
+    if height == subtree.height + 1 {
+        return node.merge_left(subtree.root, sep);
+    }
+    let (new_node, result) = match std::mem::replace(node, Nil) {
+        Inner2 {
+            mut left,
+            right_min,
+            right,
+            ..
+        } => {
+            let result = graft_left(&mut left, height - 1, subtree, sep);
+            absorb_inner2(0, result, left, right_min, right)
+        }
+        Inner3 {
+            mut left,
+            middle_min,
+            middle,
+            right_min,
+            right,
+            ..
+        } => {
+            let result = graft_left(&mut left, height - 1, subtree, sep);
+            absorb_inner3(0, result, left, middle_min, middle, right_min, right)
+        }
+        _ => panic!("graft_left must stop at an inner node"),
+    };
+    *node = new_node;
+    result
+}
+
+// Splits a tree of the given height into the entries with keys less than
+// `key` and those with keys at or above it.
+//
+pub fn split<K: Clone, V, S: Summary<K>, C: Compare<K>>(
+    node: Node<K, V, S>,
+    height: usize,
+    key: &K,
+    cmp: &C,
+) -> (Part<K, V, S>, Part<K, V, S>) {
+    split_by(node, height, &|k: &K| cmp.compare(k, key).is_lt())
+}
+
+// Splits a tree into the entries whose keys fall below, within and above the
+// given bounds.
+//
+pub fn split_range<K: Clone, V, S: Summary<K>, C: Compare<K>>(
+    node: Node<K, V, S>,
+    height: usize,
+    lower: Bound<&K>,
+    upper: Bound<&K>,
+    cmp: &C,
+) -> [Part<K, V, S>; 3] {
+    let (below, rest) = match lower {
+        Unbounded => (Part::empty(), Part::new(Box::new(node), height)),
+        Included(start) => split_by(node, height, &|k: &K| cmp.compare(k, start).is_lt()),
+        Excluded(start) => split_by(node, height, &|k: &K| cmp.compare(k, start).is_le()),
+    };
+    let (within, above) = match upper {
+        Unbounded => (rest, Part::empty()),
+        Included(end) => split_by(*rest.root, rest.height, &|k: &K| {
+            cmp.compare(k, end).is_le()
+        }),
+        Excluded(end) => split_by(*rest.root, rest.height, &|k: &K| {
+            cmp.compare(k, end).is_lt()
+        }),
+    };
+    [below, within, above]
+}
+
+// Splits a tree of the given height into the entries whose keys satisfy
+// `goes_left` and those that don't; the predicate must hold for some prefix
+// of the keys in order and for none after.  Each level joins its untouched
+// children onto the halves split from the child below; the heights involved
+// shrink as the halves grow, so the whole split costs O(height).
+//
+fn split_by<K: Clone, V, S: Summary<K>, F: Fn(&K) -> bool>(
+    node: Node<K, V, S>,
+    height: usize,
+    goes_left: &F,
+) -> (Part<K, V, S>, Part<K, V, S>) {
+    match node {
+        Nil => (Part::empty(), Part::empty()),
+
+        Leaf2 { val } => {
+            if goes_left(&val.0) {
+                (Part::new(Box::new(Leaf2 { val }), 1), Part::empty())
+            } else {
+                (Part::empty(), Part::new(Box::new(Leaf2 { val }), 1))
+            }
+        }
+
+        Leaf3 { val1, val2 } => {
+            if !goes_left(&val1.0) {
+                (Part::empty(), Part::new(Box::new(Leaf3 { val1, val2 }), 1))
+            } else if goes_left(&val2.0) {
+                (Part::new(Box::new(Leaf3 { val1, val2 }), 1), Part::empty())
+            } else {
+                (
+                    Part::new(Box::new(Leaf2 { val: val1 }), 1),
+                    Part::new(Box::new(Leaf2 { val: val2 }), 1),
+                )
+            }
+        }
+
+        Inner2 {
+            left,
+            right_min,
+            right,
+            ..
+        } => {
+            let child = height - 1;
+            if goes_left(&right_min) {
+                let (lower, upper) = split_by(*right, child, goes_left);
+                (join(Part::new(left, child), right_min, lower), upper)
+            } else {
+                let (lower, upper) = split_by(*left, child, goes_left);
+                (lower, join(upper, right_min, Part::new(right, child)))
+            }
+        }
+
+        Inner3 {
+            left,
+            middle_min,
+            middle,
+            right_min,
+            right,
+            ..
+        } => {
+            let child = height - 1;
+            if goes_left(&right_min) {
+                let (lower, upper) = split_by(*right, child, goes_left);
+                let rest = Box::new(Node::inner2(left, middle_min, middle));
+                (join(Part::new(rest, height), right_min, lower), upper)
+            } else if goes_left(&middle_min) {
+                let (lower, upper) = split_by(*middle, child, goes_left);
+                (
+                    join(Part::new(left, child), middle_min, lower),
+                    join(upper, right_min, Part::new(right, child)),
+                )
+            } else {
+                let (lower, upper) = split_by(*left, child, goes_left);
+                let rest = Box::new(Node::inner2(middle, right_min, right));
+                (lower, join(upper, middle_min, Part::new(rest, height)))
+            }
+        }
+    }
+}
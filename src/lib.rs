@@ -1,87 +1,155 @@
-#![allow(dead_code)]
-#![allow(unused_variables)]
-#![allow(unused_mut)]
-#![allow(unused_imports)]
+//! Ordered sets and maps backed by 2-3 trees.
+//!
+//! ```
+//! use two3tree::{Tree, TreeMap};
+//!
+//! let mut set = Tree::new();
+//! set.insert(3);
+//! set.insert(1);
+//! assert_eq!(set.find(&3), Some(&3));
+//!
+//! let mut map = TreeMap::new();
+//! map.insert("a", 1);
+//! assert_eq!(map.insert("a", 2), Some(1));
+//! assert_eq!(map.get(&"a"), Some(&2));
+//! ```
+
+mod compare;
+pub use crate::compare::{Compare, Natural};
+
+mod summary;
+pub use crate::summary::Summary;
+
+mod build;
 
 mod node;
 use crate::node::*;
 
 mod insert;
-use crate::insert::{insert_inner2, insert_inner3, insert_leaf3, InsertResult};
+use crate::insert::{
+    absorb_inner2, absorb_inner3, insert_inner2, insert_inner3, insert_leaf3, split_leaf3,
+    InsertResult,
+};
 
 mod remove;
-use crate::remove::RemoveResult;
+pub use crate::remove::RemoveEvent;
+use crate::remove::{RemoveResult, Target};
+
+mod iter;
+
+mod join;
+
+mod setops;
+use crate::setops::SetOp;
+
+pub mod map;
+pub use crate::map::TreeMap;
+
+mod multiset;
+pub use crate::multiset::TreeMultiset;
+
+pub mod interval_tree;
+pub use crate::interval_tree::IntervalTree;
+
+pub mod range_set;
+pub use crate::range_set::RangeSet;
+
+use gcollections::ops::{
+    Cardinality, Contains, Difference, Disjoint, Empty, Intersection, Subset, Union,
+};
+use gcollections::Collection;
+
+use std::cmp::Ordering;
+use std::iter::{FromIterator, FusedIterator};
+use std::ops::Bound::{self, Excluded, Included, Unbounded};
+use std::ops::{BitAnd, BitOr, BitXor, RangeBounds, Sub};
 
 use Node::{Inner2, Inner3, Leaf2, Leaf3, Nil};
 
-impl Node {
-    fn insert(&mut self, new_val: i32) -> InsertResult {
-        use InsertResult::{Ok, Split};
+impl<K: Clone, V, S: Summary<K>> Node<K, V, S> {
+    fn insert<C: Compare<K>>(&mut self, new_val: (K, V), cmp: &C) -> InsertResult<K, V, S> {
+        use InsertResult::{Ok, Replaced};
         match std::mem::replace(self, Nil) {
             Nil => {
                 *self = Leaf2 { val: new_val };
                 Ok
             }
 
-            Leaf2 { val } => {
-                *self = Leaf3 {
-                    val1: std::cmp::min(new_val, val),
-                    val2: std::cmp::max(new_val, val),
-                };
-                Ok
-            }
+            Leaf2 { mut val } => match cmp.compare(&new_val.0, &val.0) {
+                Ordering::Equal => {
+                    let old = std::mem::replace(&mut val.1, new_val.1);
+                    *self = Leaf2 { val };
+                    Replaced(old)
+                }
+                Ordering::Less => {
+                    *self = Leaf3 {
+                        val1: new_val,
+                        val2: val,
+                    };
+                    Ok
+                }
+                Ordering::Greater => {
+                    *self = Leaf3 {
+                        val1: val,
+                        val2: new_val,
+                    };
+                    Ok
+                }
+            },
 
             Leaf3 { val1, val2 } => {
-                let (node, result) = insert_leaf3(new_val, (val1, val2));
+                let (node, result) = insert_leaf3(new_val, (val1, val2), cmp);
                 *self = node;
-                return result;
+                result
             }
 
             Inner2 {
-                mut left,
+                left,
                 right_min,
-                mut right,
+                right,
+                ..
             } => {
-                let (node, result) = insert_inner2(new_val, left, right_min, right);
+                let (node, result) = insert_inner2(new_val, left, right_min, right, cmp);
                 *self = node;
-                return result;
+                result
             }
 
             Inner3 {
-                mut left,
+                left,
                 middle_min,
-                mut middle,
+                middle,
                 right_min,
-                mut right,
+                right,
+                ..
             } => {
                 let (node, result) =
-                    insert_inner3(new_val, left, middle_min, middle, right_min, right);
+                    insert_inner3(new_val, left, middle_min, middle, right_min, right, cmp);
                 *self = node;
-                return result;
+                result
             }
         }
     }
 
-    fn remove(&mut self, rm_val: i32) -> RemoveResult {
+    fn remove<C: Compare<K>>(&mut self, target: Target<K>, cmp: &C) -> RemoveResult<K, V, S> {
         use RemoveResult::{Drained, NotFound, Ok, Orphaned};
         match std::mem::replace(self, Nil) {
             Nil => NotFound,
             Leaf2 { val } => {
-                if rm_val == val {
+                if target.matches(&val.0, true, true, cmp) {
                     // self stays Nil
-                    Drained
+                    Drained(val)
                 } else {
                     *self = Leaf2 { val };
                     NotFound
                 }
             }
             Leaf3 { val1, val2 } => {
-                if rm_val == val1 {
+                if target.matches(&val1.0, true, false, cmp) {
                     *self = Leaf2 { val: val2 };
-                    Ok
-                } else if rm_val == val2 {
+                    Ok(val1)
+                } else if target.matches(&val2.0, false, true, cmp) {
                     *self = Leaf2 { val: val1 };
-                    Ok
+                    Ok(val2)
                 } else {
                     *self = Leaf3 { val1, val2 };
                     NotFound
@@ -91,56 +159,47 @@ impl Node {
                 mut left,
                 right_min,
                 mut right,
+                ..
             } => {
-                let result = if rm_val < right_min {
-                    match left.remove(rm_val) {
+                let result = if target.is_left_of(&right_min, 1, cmp) {
+                    match left.remove(target.below(), cmp) {
                         NotFound => NotFound,
-                        Ok => Ok,
-                        Drained => {
-                            return Orphaned(right);
+                        Ok(val) => Ok(val),
+                        Drained(val) => {
+                            return Orphaned(val, right);
                         }
-                        Orphaned(to_merge) => match right.merge_left(to_merge, right_min) {
+                        Orphaned(val, to_merge) => match right.merge_left(to_merge, right_min) {
                             InsertResult::Ok => {
-                                return Orphaned(right);
+                                return Orphaned(val, right);
                             }
+                            InsertResult::Replaced(_) => unreachable!(),
                             InsertResult::Split(split_min, split) => {
-                                *self = Inner2 {
-                                    left: right,
-                                    right_min: split_min,
-                                    right: split,
-                                };
-                                return Ok;
+                                *self = Node::inner2(right, split_min, split);
+                                return Ok(val);
                             }
                         },
                     }
                 } else {
-                    match right.remove(rm_val) {
+                    match right.remove(target.below(), cmp) {
                         NotFound => NotFound,
-                        Ok => Ok,
-                        Drained => {
-                            return Orphaned(left);
+                        Ok(val) => Ok(val),
+                        Drained(val) => {
+                            return Orphaned(val, left);
                         }
-                        Orphaned(to_merge) => match left.merge_right(right_min, to_merge) {
+                        Orphaned(val, to_merge) => match left.merge_right(right_min, to_merge) {
                             InsertResult::Ok => {
-                                return Orphaned(left);
+                                return Orphaned(val, left);
                             }
+                            InsertResult::Replaced(_) => unreachable!(),
                             InsertResult::Split(split_min, split) => {
-                                *self = Inner2 {
-                                    left,
-                                    right_min: split_min,
-                                    right: split,
-                                };
-                                return Ok;
+                                *self = Node::inner2(left, split_min, split);
+                                return Ok(val);
                             }
                         },
                     }
                 };
-                *self = Inner2 {
-                    left,
-                    right_min,
-                    right,
-                };
-                return result;
+                *self = Node::inner2(left, right_min, right);
+                result
             }
             Inner3 {
                 mut left,
@@ -148,139 +207,92 @@ impl Node {
                 mut middle,
                 right_min,
                 mut right,
+                ..
             } => {
-                let result = if rm_val < middle_min {
-                    match left.remove(rm_val) {
-                        Ok => Ok,
+                let result = if target.is_left_of(&middle_min, 1, cmp) {
+                    match left.remove(target.below(), cmp) {
+                        Ok(val) => Ok(val),
                         NotFound => NotFound,
-                        Drained => {
-                            *self = Inner2 {
-                                left: middle,
-                                right_min,
-                                right,
-                            };
-                            return Ok;
+                        Drained(val) => {
+                            *self = Node::inner2(middle, right_min, right);
+                            return Ok(val);
                         }
-                        Orphaned(to_merge) => {
+                        Orphaned(val, to_merge) => {
                             match middle.merge_left(to_merge, middle_min) {
                                 InsertResult::Ok => {
-                                    *self = Inner2 {
-                                        left: middle,
-                                        right_min,
-                                        right,
-                                    };
+                                    *self = Node::inner2(middle, right_min, right);
                                 }
+                                InsertResult::Replaced(_) => unreachable!(),
                                 InsertResult::Split(split_min, split) => {
-                                    *self = Inner3 {
-                                        left: middle,
-                                        middle_min: split_min,
-                                        middle: split,
-                                        right_min,
-                                        right,
-                                    };
+                                    *self =
+                                        Node::inner3(middle, split_min, split, right_min, right);
                                 }
                             }
-                            return Ok;
+                            return Ok(val);
                         }
                     }
-                } else if rm_val < right_min {
-                    match middle.remove(rm_val) {
-                        Ok => Ok,
+                } else if target.is_left_of(&right_min, 2, cmp) {
+                    match middle.remove(target.below(), cmp) {
+                        Ok(val) => Ok(val),
                         NotFound => NotFound,
-                        Drained => {
-                            *self = Inner2 {
-                                left,
-                                right_min,
-                                right,
-                            };
-                            return Ok;
+                        Drained(val) => {
+                            *self = Node::inner2(left, right_min, right);
+                            return Ok(val);
                         }
-                        Orphaned(to_merge) => {
+                        Orphaned(val, to_merge) => {
                             match right.merge_left(to_merge, right_min) {
                                 InsertResult::Ok => {
-                                    *self = Inner2 {
-                                        left,
-                                        right_min,
-                                        right,
-                                    };
+                                    *self = Node::inner2(left, middle_min, right);
                                 }
+                                InsertResult::Replaced(_) => unreachable!(),
                                 InsertResult::Split(split_min, split) => {
-                                    *self = Inner3 {
-                                        left,
-                                        middle_min: right_min,
-                                        middle: right,
-                                        right_min: split_min,
-                                        right: split,
-                                    };
+                                    *self = Node::inner3(left, middle_min, right, split_min, split);
                                 }
                             }
-                            return Ok;
+                            return Ok(val);
                         }
                     }
                 } else {
-                    match right.remove(rm_val) {
-                        Ok => Ok,
+                    match right.remove(target.below(), cmp) {
+                        Ok(val) => Ok(val),
                         NotFound => NotFound,
-                        Drained => {
-                            *self = Inner2 {
-                                left,
-                                right_min: middle_min,
-                                right: middle,
-                            };
-                            return Ok;
+                        Drained(val) => {
+                            *self = Node::inner2(left, middle_min, middle);
+                            return Ok(val);
                         }
-                        Orphaned(to_merge) => {
+                        Orphaned(val, to_merge) => {
                             match middle.merge_right(right_min, to_merge) {
                                 InsertResult::Ok => {
-                                    *self = Inner2 {
-                                        left,
-                                        right_min: middle_min,
-                                        right: middle,
-                                    };
+                                    *self = Node::inner2(left, middle_min, middle);
                                 }
+                                InsertResult::Replaced(_) => unreachable!(),
                                 InsertResult::Split(split_min, split) => {
-                                    *self = Inner3 {
-                                        left,
-                                        middle_min,
-                                        middle,
-                                        right_min: split_min,
-                                        right: split,
-                                    };
+                                    *self =
+                                        Node::inner3(left, middle_min, middle, split_min, split);
                                 }
                             }
-                            return Ok;
+                            return Ok(val);
                         }
                     }
                 };
-                *self = Inner3 {
-                    left,
-                    middle_min,
-                    middle,
-                    right_min,
-                    right,
-                };
-                return result;
+                *self = Node::inner3(left, middle_min, middle, right_min, right);
+                result
             }
         }
     }
 
     // Merges subtree as a child on the left side of this node; may result in a split.
     //
-    fn merge_left(&mut self, subtree: Box<Node>, left_min: i32) -> InsertResult {
+    fn merge_left(&mut self, subtree: Box<Node<K, V, S>>, left_min: K) -> InsertResult<K, V, S> {
         let node = std::mem::replace(self, Nil);
         if let Inner2 {
             left,
             right_min,
             right,
+            ..
         } = node
         {
-            *self = Inner3 {
-                left: subtree,
-                middle_min: left_min,
-                middle: left,
-                right_min,
-                right,
-            };
+            *self = Node::inner3(subtree, left_min, left, right_min, right);
             return InsertResult::Ok;
         }
         if let Inner3 {
@@ -289,40 +301,32 @@ impl Node {
             middle,
             right_min,
             right,
+            ..
         } = node
         {
-            *self = Inner2 {
-                left: subtree,
-                right_min: left_min,
-                right: left,
-            };
+            *self = Node::inner2(subtree, left_min, left);
             return InsertResult::Split(
                 middle_min,
-                Box::new(Inner2 {
-                    left: middle,
-                    right_min,
-                    right,
-                }),
+                Box::new(Node::inner2(middle, right_min, right)),
             );
         }
         panic!("insert_subtree may only be called on an inner node!")
     }
 
-    fn merge_right(&mut self, subtree_min: i32, subtree: Box<Node>) -> InsertResult {
+    fn merge_right(
+        &mut self,
+        subtree_min: K,
+        subtree: Box<Node<K, V, S>>,
+    ) -> InsertResult<K, V, S> {
         let node = std::mem::replace(self, Nil);
         if let Inner2 {
             left,
             right_min,
             right,
+            ..
         } = node
         {
-            *self = Inner3 {
-                left,
-                middle_min: right_min,
-                middle: right,
-                right_min: subtree_min,
-                right: subtree,
-            };
+            *self = Node::inner3(left, right_min, right, subtree_min, subtree);
             return InsertResult::Ok;
         }
         if let Inner3 {
@@ -331,31 +335,24 @@ impl Node {
             middle,
             right_min,
             right,
+            ..
         } = node
         {
-            *self = Inner2 {
-                left,
-                right_min: middle_min,
-                right: middle,
-            };
+            *self = Node::inner2(left, middle_min, middle);
             return InsertResult::Split(
                 right_min,
-                Box::new(Inner2 {
-                    left: right,
-                    right_min: subtree_min,
-                    right: subtree,
-                }),
+                Box::new(Node::inner2(right, subtree_min, subtree)),
             );
         }
         panic!("insert_subtree may only be called on an inner node!")
     }
 
-    fn find<'a>(&'a self, key: i32) -> Option<&'a i32> {
+    fn find<C: Compare<K>>(&self, key: &K, cmp: &C) -> Option<&(K, V)> {
         match self {
             Nil => None,
 
             Leaf2 { val } => {
-                if key == *val {
+                if cmp.compare(key, &val.0).is_eq() {
                     Some(val)
                 } else {
                     None
@@ -363,9 +360,9 @@ impl Node {
             }
 
             Leaf3 { val1, val2 } => {
-                if key == *val1 {
+                if cmp.compare(key, &val1.0).is_eq() {
                     Some(val1)
-                } else if key == *val2 {
+                } else if cmp.compare(key, &val2.0).is_eq() {
                     Some(val2)
                 } else {
                     None
@@ -376,11 +373,12 @@ impl Node {
                 left,
                 right_min,
                 right,
+                ..
             } => {
-                if key < *right_min {
-                    left.find(key)
+                if cmp.compare(key, right_min).is_lt() {
+                    left.find(key, cmp)
                 } else {
-                    right.find(key)
+                    right.find(key, cmp)
                 }
             }
 
@@ -390,140 +388,2156 @@ impl Node {
                 middle,
                 right_min,
                 right,
+                ..
             } => {
-                if key < *middle_min {
-                    left.find(key)
-                } else if key < *right_min {
-                    middle.find(key)
+                if cmp.compare(key, middle_min).is_lt() {
+                    left.find(key, cmp)
+                } else if cmp.compare(key, right_min).is_lt() {
+                    middle.find(key, cmp)
                 } else {
-                    right.find(key)
+                    right.find(key, cmp)
                 }
             }
         }
     }
 
-    fn height(&self) -> usize {
+    fn find_mut<C: Compare<K>>(&mut self, key: &K, cmp: &C) -> Option<&mut (K, V)> {
         match self {
-            Nil => 0,
-            Leaf2 { .. } => 1,
-            Leaf3 { .. } => 1,
-            Inner2 { left, .. } => left.height() + 1,
-            Inner3 { left, .. } => left.height() + 1,
-        }
-    }
-}
-
-#[derive(Debug)]
-struct Tree {
-    root: Box<Node>,
-}
+            Nil => None,
 
-impl Tree {
-    fn new() -> Self {
-        Self {
-            root: Box::new(Nil),
-        }
-    }
-    fn insert(&mut self, val: i32) {
-        match self.root.insert(val) {
-            InsertResult::Ok => {}
-            InsertResult::Split(split_min, split) => {
-                let tmp = std::mem::replace(&mut self.root, Box::new(Nil));
-                self.root = Box::new(Inner2 {
-                    left: tmp,
-                    right_min: split_min,
-                    right: split,
-                });
-            }
-        }
-    }
-    fn remove(&mut self, val: i32) {
-        use RemoveResult::{Drained, NotFound, Ok, Orphaned};
-        match self.root.remove(val) {
-            NotFound => {
-                println!("remove => NotFound");
+            Leaf2 { val } => {
+                if cmp.compare(key, &val.0).is_eq() {
+                    Some(val)
+                } else {
+                    None
+                }
             }
-            Ok => {
-                println!("remove => Ok");
+
+            Leaf3 { val1, val2 } => {
+                if cmp.compare(key, &val1.0).is_eq() {
+                    Some(val1)
+                } else if cmp.compare(key, &val2.0).is_eq() {
+                    Some(val2)
+                } else {
+                    None
+                }
             }
-            Drained => {
-                std::mem::replace(&mut self.root, Box::new(Nil));
-                println!("remove => Drained");
+
+            Inner2 {
+                left,
+                right_min,
+                right,
+                ..
+            } => {
+                if cmp.compare(key, right_min).is_lt() {
+                    left.find_mut(key, cmp)
+                } else {
+                    right.find_mut(key, cmp)
+                }
             }
-            Orphaned(new_root) => {
-                std::mem::replace(&mut self.root, new_root);
-                println!("remove => Orphaned");
+
+            Inner3 {
+                left,
+                middle_min,
+                middle,
+                right_min,
+                right,
+                ..
+            } => {
+                if cmp.compare(key, middle_min).is_lt() {
+                    left.find_mut(key, cmp)
+                } else if cmp.compare(key, right_min).is_lt() {
+                    middle.find_mut(key, cmp)
+                } else {
+                    right.find_mut(key, cmp)
+                }
             }
         }
     }
-    fn find<'a>(&'a self, val: i32) -> Option<&'a i32> {
-        self.root.find(val)
-    }
-    fn height(&self) -> usize {
-        self.root.height()
+
+    // Records the child index taken at each level on the way down to `key`,
+    // ending with a position in the leaf.  Returns true if the key is there, in
+    // which case the last index is its slot in the leaf; otherwise it is the
+    // slot a new entry for the key would take.
+    //
+    fn search<C: Compare<K>>(&self, key: &K, cmp: &C, path: &mut Vec<usize>) -> bool {
+        let mut node = self;
+        loop {
+            match node {
+                Nil => {
+                    path.push(0);
+                    return false;
+                }
+                Leaf2 { val } => {
+                    let order = cmp.compare(key, &val.0);
+                    path.push(if order.is_gt() { 1 } else { 0 });
+                    return order.is_eq();
+                }
+                Leaf3 { val1, val2 } => {
+                    let order1 = cmp.compare(key, &val1.0);
+                    let order2 = cmp.compare(key, &val2.0);
+                    if order1.is_le() {
+                        path.push(0);
+                        return order1.is_eq();
+                    }
+                    path.push(if order2.is_le() { 1 } else { 2 });
+                    return order2.is_eq();
+                }
+                Inner2 {
+                    left,
+                    right_min,
+                    right,
+                    ..
+                } => {
+                    if cmp.compare(key, right_min).is_lt() {
+                        path.push(0);
+                        node = left;
+                    } else {
+                        path.push(1);
+                        node = right;
+                    }
+                }
+                Inner3 {
+                    left,
+                    middle_min,
+                    middle,
+                    right_min,
+                    right,
+                    ..
+                } => {
+                    if cmp.compare(key, middle_min).is_lt() {
+                        path.push(0);
+                        node = left;
+                    } else if cmp.compare(key, right_min).is_lt() {
+                        path.push(1);
+                        node = middle;
+                    } else {
+                        path.push(2);
+                        node = right;
+                    }
+                }
+            }
+        }
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+    // Inserts `new_val` at a position recorded by `search` that did not find
+    // its key, following the path instead of comparing keys.
+    //
+    // Also works out where the new entry ended up: the index it has at each
+    // level is pushed onto `loc`, bottom up, and the returned flag says
+    // whether it landed in the node split off from this one.
+    //
+    fn insert_at(
+        &mut self,
+        path: &[usize],
+        new_val: (K, V),
+        loc: &mut Vec<usize>,
+    ) -> (InsertResult<K, V, S>, bool) {
+        use InsertResult::{Ok, Split};
+        let (&index, path) = path.split_first().expect("path ends before a leaf");
+        match std::mem::replace(self, Nil) {
+            Nil => {
+                *self = Leaf2 { val: new_val };
+                loc.push(0);
+                (Ok, false)
+            }
 
-    #[test]
-    fn insert_test() {
-        assert_eq!(2 + 2, 4);
+            Leaf2 { val } => {
+                *self = if index == 0 {
+                    Leaf3 {
+                        val1: new_val,
+                        val2: val,
+                    }
+                } else {
+                    Leaf3 {
+                        val1: val,
+                        val2: new_val,
+                    }
+                };
+                loc.push(index);
+                (Ok, false)
+            }
 
-        let mut t = Tree::new();
+            Leaf3 { val1, val2 } => {
+                let (node, result) = split_leaf3(index, new_val, (val1, val2));
+                *self = node;
+                if index == 0 {
+                    loc.push(0);
+                    (result, false)
+                } else {
+                    loc.push(index - 1);
+                    (result, true)
+                }
+            }
 
-        assert!(t.find(10) == None);
+            Inner2 {
+                mut left,
+                right_min,
+                mut right,
+                ..
+            } => {
+                let (result, in_split) = if index == 0 {
+                    left.insert_at(path, new_val, loc)
+                } else {
+                    right.insert_at(path, new_val, loc)
+                };
+                let (node, result) = absorb_inner2(index, result, left, right_min, right);
+                *self = node;
+                loc.push(index + in_split as usize);
+                (result, false)
+            }
 
-        t.insert(10);
-        println!("{:#?}", t);
+            Inner3 {
+                mut left,
+                middle_min,
+                mut middle,
+                right_min,
+                mut right,
+                ..
+            } => {
+                let (result, in_split) = match index {
+                    0 => left.insert_at(path, new_val, loc),
+                    1 => middle.insert_at(path, new_val, loc),
+                    _ => right.insert_at(path, new_val, loc),
+                };
+                let (node, result) =
+                    absorb_inner3(index, result, left, middle_min, middle, right_min, right);
+                *self = node;
+                // A split leaves two of the four children here and moves two
+                // to the new sibling.
+                let child = index + in_split as usize;
+                if matches!(result, Split(..)) && child >= 2 {
+                    loc.push(child - 2);
+                    (result, true)
+                } else {
+                    loc.push(child);
+                    (result, false)
+                }
+            }
+        }
+    }
 
-        assert_eq!(t.find(10), Some(&10));
+    fn entry_at(&self, path: &[usize]) -> &(K, V) {
+        match (self, path) {
+            (Leaf2 { val }, _) => val,
+            (Leaf3 { val1, .. }, [0]) => val1,
+            (Leaf3 { val2, .. }, _) => val2,
+            (Inner2 { left, .. }, [0, rest @ ..]) => left.entry_at(rest),
+            (Inner2 { right, .. }, [_, rest @ ..]) => right.entry_at(rest),
+            (Inner3 { left, .. }, [0, rest @ ..]) => left.entry_at(rest),
+            (Inner3 { middle, .. }, [1, rest @ ..]) => middle.entry_at(rest),
+            (Inner3 { right, .. }, [_, rest @ ..]) => right.entry_at(rest),
+            _ => panic!("path does not lead to an entry"),
+        }
+    }
 
-        for k in 0..1000 {
-            t.insert(k);
+    fn entry_at_mut(&mut self, path: &[usize]) -> &mut (K, V) {
+        match (self, path) {
+            (Leaf2 { val }, _) => val,
+            (Leaf3 { val1, .. }, [0]) => val1,
+            (Leaf3 { val2, .. }, _) => val2,
+            (Inner2 { left, .. }, [0, rest @ ..]) => left.entry_at_mut(rest),
+            (Inner2 { right, .. }, [_, rest @ ..]) => right.entry_at_mut(rest),
+            (Inner3 { left, .. }, [0, rest @ ..]) => left.entry_at_mut(rest),
+            (Inner3 { middle, .. }, [1, rest @ ..]) => middle.entry_at_mut(rest),
+            (Inner3 { right, .. }, [_, rest @ ..]) => right.entry_at_mut(rest),
+            _ => panic!("path does not lead to an entry"),
         }
+    }
 
-        for k in 0..1000 {
-            assert!(t.find(k) == Some(&k));
+    fn child(&self, index: usize) -> Option<&Node<K, V, S>> {
+        match (self, index) {
+            (Inner2 { left, .. }, 0) => Some(left),
+            (Inner2 { right, .. }, 1) => Some(right),
+            (Inner3 { left, .. }, 0) => Some(left),
+            (Inner3 { middle, .. }, 1) => Some(middle),
+            (Inner3 { right, .. }, 2) => Some(right),
+            _ => None,
         }
+    }
 
-        assert_eq!(t.height(), 10);
+    // The separator just left of the child picked by the last index of
+    // `path`, in the inner node that the rest of `path` leads to.
+    //
+    fn separator_at(&self, path: &[usize]) -> &K {
+        match (self, path) {
+            (Inner2 { right_min, .. }, [1]) => right_min,
+            (Inner3 { middle_min, .. }, [1]) => middle_min,
+            (Inner3 { right_min, .. }, [2]) => right_min,
+            (_, [index, rest @ ..]) if !rest.is_empty() => self
+                .child(*index)
+                .expect("path leads through inner nodes")
+                .separator_at(rest),
+            _ => panic!("path does not end at a separator"),
+        }
+    }
 
-        for k in 1000..100000 {
-            t.insert(k);
+    // The position in key order of the entry that `path` leads to, counted
+    // from the cached sizes of the subtrees to its left.
+    //
+    fn rank_at(&self, path: &[usize]) -> usize {
+        let mut node = self;
+        let mut rank = 0;
+        for &index in path {
+            match node.child(index) {
+                Some(child) => {
+                    rank += (0..index)
+                        .map(|i| node.child(i).map_or(0, Node::size))
+                        .sum::<usize>();
+                    node = child;
+                }
+                None => return rank + index,
+            }
         }
+        rank
+    }
 
-        for k in 1000..100000 {
-            assert!(t.find(k) == Some(&k));
+    // Records in `path` the way down to the entry at position `index` in key
+    // order, as `select` finds it.
+    //
+    fn select_path(&self, mut index: usize, path: &mut Vec<usize>) {
+        let mut node = self;
+        loop {
+            match node {
+                Nil => return,
+                Leaf2 { .. } | Leaf3 { .. } => {
+                    path.push(index);
+                    return;
+                }
+                _ => {
+                    let mut slot = 0;
+                    while let Some(child) = node.child(slot) {
+                        if index < child.size() || node.child(slot + 1).is_none() {
+                            break;
+                        }
+                        index -= child.size();
+                        slot += 1;
+                    }
+                    path.push(slot);
+                    node = node.child(slot).expect("slot is a child");
+                }
+            }
         }
+    }
 
-        assert_eq!(t.height(), 17);
+    // Returns the number of entries in the leaf that `path` leads to.
+    //
+    fn leaf_len_at(&self, path: &[usize]) -> usize {
+        match (self, path) {
+            (Nil, _) => 0,
+            (Leaf2 { .. }, _) => 1,
+            (Leaf3 { .. }, _) => 2,
+            (_, [index, rest @ ..]) => self
+                .child(*index)
+                .map_or(0, |child| child.leaf_len_at(rest)),
+            (_, []) => 0,
+        }
     }
 
-    #[test]
-    fn remove_test() {
-        let mut t = Tree::new();
+    // Extends `path` down to the first (or, if not `forward`, the last) entry
+    // of this subtree.
+    //
+    fn edge_path(&self, path: &mut Vec<usize>, forward: bool) {
+        let mut node = self;
+        loop {
+            let index = match node {
+                Nil => return,
+                Leaf2 { .. } => 0,
+                Leaf3 { .. } if forward => 0,
+                Leaf3 { .. } => 1,
+                Inner2 { .. } if !forward => 1,
+                Inner3 { .. } if !forward => 2,
+                _ => 0,
+            };
+            path.push(index);
+            match node.child(index) {
+                Some(child) => node = child,
+                None => return,
+            }
+        }
+    }
 
-        for k in 0..100000 {
-            t.insert(k);
+    // Moves `path`, which leads to an entry, on to the next entry in order
+    // (or the previous one, if not `forward`) by index arithmetic alone.
+    // Returns false, leaving `path` as it was, if there is no such entry.
+    //
+    fn step_path(&self, path: &mut Vec<usize>, depth: usize, forward: bool) -> bool {
+        let index = path[depth];
+        if let Leaf3 { .. } = self {
+            return match (forward, index) {
+                (true, 0) => {
+                    path[depth] = 1;
+                    true
+                }
+                (false, 1) => {
+                    path[depth] = 0;
+                    true
+                }
+                _ => false,
+            };
         }
+        match self.child(index) {
+            Some(child) if child.step_path(path, depth + 1, forward) => return true,
+            Some(_) => {}
+            None => return false,
+        }
+        let sibling = if forward {
+            Some(index + 1)
+        } else {
+            index.checked_sub(1)
+        };
+        match sibling.and_then(|sibling| Some((sibling, self.child(sibling)?))) {
+            Some((sibling, child)) => {
+                path.truncate(depth);
+                path.push(sibling);
+                child.edge_path(path, forward);
+                true
+            }
+            None => false,
+        }
+    }
 
-        for k in 0..100000 {
-            assert!(t.find(k) == Some(&k));
+    fn first(&self) -> Option<&(K, V)> {
+        match self {
+            Nil => None,
+            Leaf2 { val } => Some(val),
+            Leaf3 { val1, .. } => Some(val1),
+            Inner2 { left, .. } => left.first(),
+            Inner3 { left, .. } => left.first(),
         }
+    }
 
-        assert_eq!(t.height(), 17);
+    fn last(&self) -> Option<&(K, V)> {
+        match self {
+            Nil => None,
+            Leaf2 { val } => Some(val),
+            Leaf3 { val2, .. } => Some(val2),
+            Inner2 { right, .. } => right.last(),
+            Inner3 { right, .. } => right.last(),
+        }
+    }
 
-        for k in 0..100000 {
-            assert!(t.find(k) == Some(&k));
-            t.remove(k);
-            assert!(t.find(k) == None, "k={}, tree={:#?}", k, t);
+    // Finds the greatest entry before `key` (or equal to it, if `inclusive`).
+    //
+    // Descends once, remembering the nearest subtree to the left of the path;
+    // if the leaf at the bottom holds no match, the answer is the last entry
+    // of that subtree.
+    //
+    fn last_before<C: Compare<K>>(&self, key: &K, inclusive: bool, cmp: &C) -> Option<&(K, V)> {
+        let before = |other: &K| match cmp.compare(other, key) {
+            Ordering::Less => true,
+            Ordering::Equal => inclusive,
+            Ordering::Greater => false,
+        };
+        let mut node = self;
+        let mut fallback = None;
+        loop {
+            match node {
+                Nil => return None,
+                Leaf2 { val } => {
+                    if before(&val.0) {
+                        return Some(val);
+                    }
+                    break;
+                }
+                Leaf3 { val1, val2 } => {
+                    if before(&val2.0) {
+                        return Some(val2);
+                    } else if before(&val1.0) {
+                        return Some(val1);
+                    }
+                    break;
+                }
+                Inner2 {
+                    left,
+                    right_min,
+                    right,
+                    ..
+                } => {
+                    if before(right_min) {
+                        fallback = Some(left);
+                        node = right;
+                    } else {
+                        node = left;
+                    }
+                }
+                Inner3 {
+                    left,
+                    middle_min,
+                    middle,
+                    right_min,
+                    right,
+                    ..
+                } => {
+                    if before(right_min) {
+                        fallback = Some(middle);
+                        node = right;
+                    } else if before(middle_min) {
+                        fallback = Some(left);
+                        node = middle;
+                    } else {
+                        node = left;
+                    }
+                }
+            }
         }
+        fallback.and_then(|subtree| subtree.last())
+    }
 
-        for k in 0..100000 {
-            assert!(t.find(k) == None);
+    // Finds the least entry after `key` (or equal to it, if `inclusive`); the
+    // mirror image of `last_before`.
+    //
+    fn first_after<C: Compare<K>>(&self, key: &K, inclusive: bool, cmp: &C) -> Option<&(K, V)> {
+        let after = |other: &K| match cmp.compare(other, key) {
+            Ordering::Less => false,
+            Ordering::Equal => inclusive,
+            Ordering::Greater => true,
+        };
+        let mut node = self;
+        let mut fallback = None;
+        loop {
+            match node {
+                Nil => return None,
+                Leaf2 { val } => {
+                    if after(&val.0) {
+                        return Some(val);
+                    }
+                    break;
+                }
+                Leaf3 { val1, val2 } => {
+                    if after(&val1.0) {
+                        return Some(val1);
+                    } else if after(&val2.0) {
+                        return Some(val2);
+                    }
+                    break;
+                }
+                Inner2 {
+                    left,
+                    right_min,
+                    right,
+                    ..
+                } => {
+                    if cmp.compare(key, right_min).is_lt() {
+                        fallback = Some(right);
+                        node = left;
+                    } else {
+                        node = right;
+                    }
+                }
+                Inner3 {
+                    left,
+                    middle_min,
+                    middle,
+                    right_min,
+                    right,
+                    ..
+                } => {
+                    if cmp.compare(key, middle_min).is_lt() {
+                        fallback = Some(middle);
+                        node = left;
+                    } else if cmp.compare(key, right_min).is_lt() {
+                        fallback = Some(right);
+                        node = middle;
+                    } else {
+                        node = right;
+                    }
+                }
+            }
+        }
+        fallback.and_then(|subtree| subtree.first())
+    }
+
+    // Asserts the structural invariants of the subtree: every leaf at the same
+    // depth, each separator greater than every key to its left and no greater
+    // than any key to its right, and every cached size correct.  Returns the
+    // height.
+    //
+    #[cfg(test)]
+    pub(crate) fn check<C: Compare<K>>(&self, cmp: &C) -> usize {
+        let check_sep = |before: &Node<K, V, S>, sep: &K, after: &Node<K, V, S>| {
+            assert!(cmp.compare(&before.last().unwrap().0, sep).is_lt());
+            assert!(cmp.compare(sep, &after.first().unwrap().0).is_le());
+        };
+        match self {
+            Nil => 0,
+            Leaf2 { .. } => 1,
+            Leaf3 { val1, val2 } => {
+                assert!(cmp.compare(&val1.0, &val2.0).is_lt());
+                1
+            }
+            Inner2 {
+                left,
+                right_min,
+                right,
+                size,
+                ..
+            } => {
+                check_sep(left, right_min, right);
+                assert_eq!(*size, left.size() + right.size());
+                let height = left.check(cmp);
+                assert_eq!(right.check(cmp), height);
+                height + 1
+            }
+            Inner3 {
+                left,
+                middle_min,
+                middle,
+                right_min,
+                right,
+                size,
+                ..
+            } => {
+                check_sep(left, middle_min, middle);
+                assert_eq!(*size, left.size() + middle.size() + right.size());
+                check_sep(middle, right_min, right);
+                let height = left.check(cmp);
+                assert_eq!(middle.check(cmp), height);
+                assert_eq!(right.check(cmp), height);
+                height + 1
+            }
+        }
+    }
+
+    // Counts the entries whose keys satisfy `goes_left`, which must hold for
+    // some prefix of the keys in order and for none after.  Whole subtrees
+    // are counted from their cached sizes, so only one path is descended.
+    //
+    fn count_while<F: Fn(&K) -> bool>(&self, goes_left: F) -> usize {
+        let mut node = self;
+        let mut count = 0;
+        loop {
+            match node {
+                Nil => return count,
+                Leaf2 { val } => return count + goes_left(&val.0) as usize,
+                Leaf3 { val1, val2 } => {
+                    return count + goes_left(&val1.0) as usize + goes_left(&val2.0) as usize
+                }
+                Inner2 {
+                    left,
+                    right_min,
+                    right,
+                    ..
+                } => {
+                    if goes_left(right_min) {
+                        count += left.size();
+                        node = right;
+                    } else {
+                        node = left;
+                    }
+                }
+                Inner3 {
+                    left,
+                    middle_min,
+                    middle,
+                    right_min,
+                    right,
+                    ..
+                } => {
+                    if goes_left(right_min) {
+                        count += left.size() + middle.size();
+                        node = right;
+                    } else if goes_left(middle_min) {
+                        count += left.size();
+                        node = middle;
+                    } else {
+                        node = left;
+                    }
+                }
+            }
+        }
+    }
+
+    // The summary of the keys within the bounds, given that every key in this
+    // subtree is at or after `after` and before `before`.  Subtrees that the
+    // separators place wholly inside the bounds contribute their cached
+    // summaries and those wholly outside contribute nothing; only those
+    // straddling a bound are descended into, and there are at most two of
+    // those on each level.
+    //
+    fn aggregate<C: Compare<K>>(
+        &self,
+        after: Option<&K>,
+        before: Option<&K>,
+        lower: Bound<&K>,
+        upper: Bound<&K>,
+        cmp: &C,
+    ) -> S {
+        let starts_inside = match (lower, after) {
+            (Unbounded, _) => true,
+            (Included(start), Some(after)) => cmp.compare(start, after).is_le(),
+            (Excluded(start), Some(after)) => cmp.compare(start, after).is_lt(),
+            (_, None) => false,
+        };
+        let ends_inside = match (upper, before) {
+            (Unbounded, _) => true,
+            (Included(end) | Excluded(end), Some(before)) => cmp.compare(before, end).is_le(),
+            (_, None) => false,
+        };
+        if starts_inside && ends_inside {
+            return self.summary();
+        }
+        let starts_past = match (lower, before) {
+            (Included(start) | Excluded(start), Some(before)) => cmp.compare(start, before).is_ge(),
+            _ => false,
+        };
+        let ends_short = match (upper, after) {
+            (Included(end), Some(after)) => cmp.compare(end, after).is_lt(),
+            (Excluded(end), Some(after)) => cmp.compare(end, after).is_le(),
+            _ => false,
+        };
+        if starts_past || ends_short {
+            return S::empty();
+        }
+        let in_bounds = |key: &K| {
+            (match lower {
+                Unbounded => true,
+                Included(start) => cmp.compare(key, start).is_ge(),
+                Excluded(start) => cmp.compare(key, start).is_gt(),
+            }) && (match upper {
+                Unbounded => true,
+                Included(end) => cmp.compare(key, end).is_le(),
+                Excluded(end) => cmp.compare(key, end).is_lt(),
+            })
+        };
+        let of = |val: &(K, V)| {
+            if in_bounds(&val.0) {
+                S::of(&val.0)
+            } else {
+                S::empty()
+            }
+        };
+        match self {
+            Nil => S::empty(),
+            Leaf2 { val } => of(val),
+            Leaf3 { val1, val2 } => of(val1).combine(&of(val2)),
+            Inner2 {
+                left,
+                right_min,
+                right,
+                ..
+            } => left
+                .aggregate(after, Some(right_min), lower, upper, cmp)
+                .combine(&right.aggregate(Some(right_min), before, lower, upper, cmp)),
+            Inner3 {
+                left,
+                middle_min,
+                middle,
+                right_min,
+                right,
+                ..
+            } => left
+                .aggregate(after, Some(middle_min), lower, upper, cmp)
+                .combine(&middle.aggregate(Some(middle_min), Some(right_min), lower, upper, cmp))
+                .combine(&right.aggregate(Some(right_min), before, lower, upper, cmp)),
+        }
+    }
+
+    // The entry at position `index` in key order, steering by cached sizes.
+    //
+    fn select(&self, mut index: usize) -> Option<&(K, V)> {
+        let mut node = self;
+        loop {
+            match node {
+                Nil => return None,
+                Leaf2 { val } => return if index == 0 { Some(val) } else { None },
+                Leaf3 { val1, val2 } => {
+                    return match index {
+                        0 => Some(val1),
+                        1 => Some(val2),
+                        _ => None,
+                    }
+                }
+                Inner2 { left, right, .. } => {
+                    if index < left.size() {
+                        node = left;
+                    } else {
+                        index -= left.size();
+                        node = right;
+                    }
+                }
+                Inner3 {
+                    left,
+                    middle,
+                    right,
+                    ..
+                } => {
+                    if index < left.size() {
+                        node = left;
+                    } else if index < left.size() + middle.size() {
+                        index -= left.size();
+                        node = middle;
+                    } else {
+                        index -= left.size() + middle.size();
+                        node = right;
+                    }
+                }
+            }
+        }
+    }
+
+    fn height(&self) -> usize {
+        match self {
+            Nil => 0,
+            Leaf2 { .. } => 1,
+            Leaf3 { .. } => 1,
+            Inner2 { left, .. } => left.height() + 1,
+            Inner3 { left, .. } => left.height() + 1,
+        }
+    }
+}
+
+/// An ordered set backed by a 2-3 tree.
+///
+/// Elements are ordered by the comparator `C`, which defaults to the
+/// element type's own `Ord`.
+#[derive(Clone, Debug)]
+pub struct Tree<K, C = Natural, S = ()> {
+    map: TreeMap<K, (), C, S>,
+}
+
+impl<K: Ord + Clone> Tree<K> {
+    /// Creates an empty tree.
+    pub fn new() -> Self {
+        Self {
+            map: TreeMap::new(),
+        }
+    }
+}
+
+impl<K: Ord + Clone> Tree<K> {
+    /// Builds a tree in linear time from elements in strictly ascending
+    /// order.
+    ///
+    /// Panics if the elements are not strictly ascending.
+    pub fn from_sorted<I: IntoIterator<Item = K>>(iter: I) -> Self {
+        Self::from_sorted_with_comparator(iter, Natural)
+    }
+}
+
+impl<K: Clone, C: Compare<K>> Tree<K, C> {
+    /// Builds a tree ordered by `cmp`, in linear time, from elements in
+    /// strictly ascending order under `cmp`.
+    ///
+    /// Panics if the elements are not strictly ascending.
+    pub fn from_sorted_with_comparator<I: IntoIterator<Item = K>>(iter: I, cmp: C) -> Self {
+        Self {
+            map: TreeMap::from_sorted_with_comparator(iter.into_iter().map(|key| (key, ())), cmp),
+        }
+    }
+    /// Creates an empty tree ordered by `cmp`.
+    pub fn with_comparator(cmp: C) -> Self {
+        Self {
+            map: TreeMap::with_comparator(cmp),
+        }
+    }
+}
+
+impl<K: Clone, C: Compare<K>, S: Summary<K>> Tree<K, C, S> {
+    /// Creates an empty tree ordered by `cmp` whose inner nodes cache a
+    /// summary of type `S` of their elements; see `aggregate`.
+    pub fn augmented(cmp: C) -> Self {
+        Self {
+            map: TreeMap::augmented(cmp),
+        }
+    }
+    /// Adds `val` to the tree.
+    ///
+    /// Returns false, leaving the tree unchanged, if an equal element is
+    /// already present.
+    pub fn insert(&mut self, val: K) -> bool {
+        self.map.insert(val, ()).is_none()
+    }
+    /// Removes `val` from the tree, returning the stored element if it was
+    /// present.
+    pub fn remove(&mut self, val: &K) -> Option<K> {
+        self.map.remove(val).map(|(key, _)| key)
+    }
+    /// Installs a diagnostic hook that is told how each later removal was
+    /// resolved at the root; pass `None` to uninstall it.
+    ///
+    /// As with `TreeMap::set_remove_hook`, a clone keeps the hook but trees
+    /// derived from this one by `split_off` or a set operation do not.
+    pub fn set_remove_hook(&mut self, hook: Option<fn(RemoveEvent)>) {
+        self.map.set_remove_hook(hook);
+    }
+    /// Returns a reference to the element equal to `val`, if any.
+    pub fn find(&self, val: &K) -> Option<&K> {
+        self.map.get_key_value(val).map(|(key, _)| key)
+    }
+    /// Returns the number of elements in the tree.
+    pub fn len(&self) -> usize {
+        self.map.len()
+    }
+    /// Returns true if the tree holds no elements.
+    pub fn is_empty(&self) -> bool {
+        self.map.is_empty()
+    }
+    /// Returns the number of levels in the tree; 0 when it is empty.
+    pub fn height(&self) -> usize {
+        self.map.height()
+    }
+    /// Returns an iterator over the elements of the tree in order.
+    pub fn iter(&self) -> Iter<'_, K, S> {
+        Iter {
+            inner: self.map.iter(),
+        }
+    }
+    /// Returns a cursor positioned on the least element.
+    pub fn cursor(&self) -> Cursor<'_, K, C, S> {
+        Cursor {
+            inner: self.map.cursor(),
+        }
+    }
+    /// Returns a cursor that can modify the tree, positioned on the least
+    /// element.
+    pub fn cursor_mut(&mut self) -> CursorMut<'_, K, C, S> {
+        CursorMut {
+            inner: self.map.cursor_mut(),
+        }
+    }
+    /// Returns an iterator over the elements that fall within `range`, in
+    /// order.
+    ///
+    /// Panics if the start of the range is greater than its end, or if the
+    /// two are equal and both excluded.
+    pub fn range<R: RangeBounds<K>>(&self, range: R) -> Range<'_, K, S> {
+        Range {
+            inner: self.map.range(range),
+        }
+    }
+    /// Returns the least element.
+    pub fn first(&self) -> Option<&K> {
+        self.map.first().map(|(key, _)| key)
+    }
+    /// Returns the greatest element.
+    pub fn last(&self) -> Option<&K> {
+        self.map.last().map(|(key, _)| key)
+    }
+    /// Removes and returns the least element.
+    pub fn pop_first(&mut self) -> Option<K> {
+        self.map.pop_first().map(|(key, _)| key)
+    }
+    /// Removes and returns the greatest element.
+    pub fn pop_last(&mut self) -> Option<K> {
+        self.map.pop_last().map(|(key, _)| key)
+    }
+    /// Removes every element within `range`, returning how many were
+    /// removed; see `TreeMap::remove_range`.
+    pub fn remove_range<R: RangeBounds<K>>(&mut self, range: R) -> usize {
+        self.map.remove_range(range)
+    }
+    /// Returns an iterator that removes and yields the elements within
+    /// `range` for which `pred` returns true.
+    ///
+    /// The elements left behind are rebuilt in a single pass when the
+    /// iterator is dropped; see `TreeMap::extract_if`.
+    pub fn extract_if<R, F>(&mut self, range: R, pred: F) -> ExtractIf<'_, K, C, S, F>
+    where
+        R: RangeBounds<K>,
+        F: FnMut(&K) -> bool,
+    {
+        ExtractIf {
+            extract: map::Extract::new(&mut self.map, range),
+            pred,
+        }
+    }
+    /// Keeps only the elements for which `f` returns true, visiting them in
+    /// order.
+    pub fn retain<F: FnMut(&K) -> bool>(&mut self, mut f: F) {
+        self.extract_if(.., |val| !f(val)).for_each(drop);
+    }
+    /// Splits the tree in two at `val`, returning the elements greater than
+    /// or equal to it and keeping the rest.
+    pub fn split_off(&mut self, val: &K) -> Self
+    where
+        C: Clone,
+    {
+        Self {
+            map: self.map.split_off(val),
+        }
+    }
+    /// Moves every element of `other` into this tree, leaving `other` empty.
+    ///
+    /// Panics unless every element of `other` is greater than every element
+    /// of this tree.
+    pub fn append(&mut self, other: &mut Self) {
+        self.map.append(&mut other.map);
+    }
+    /// Returns the elements in either tree, keeping the one from `self`
+    /// where both hold an equal element.
+    ///
+    /// The `into_` set operations consume both trees and reuse their nodes;
+    /// the operators on `&Tree` and the `gcollections` traits leave them be.
+    /// Trees of similar size are merged in linear time; a much smaller tree
+    /// is threaded through the larger in O(m log(n/m)).
+    pub fn into_union(self, other: Self) -> Self {
+        Self {
+            map: self.map.combine(other.map, SetOp::Union),
+        }
+    }
+    /// Returns the elements of `self` that are also in `other`.
+    pub fn into_intersection(self, other: Self) -> Self {
+        Self {
+            map: self.map.combine(other.map, SetOp::Intersection),
+        }
+    }
+    /// Returns the elements of `self` that are not in `other`.
+    pub fn into_difference(self, other: Self) -> Self {
+        Self {
+            map: self.map.combine(other.map, SetOp::Difference),
+        }
+    }
+    /// Returns the elements that are in exactly one of the two trees.
+    pub fn into_symmetric_difference(self, other: Self) -> Self {
+        Self {
+            map: self.map.combine(other.map, SetOp::SymmetricDifference),
+        }
+    }
+    /// Returns true if no element is in both trees.
+    ///
+    /// Each element of a much smaller tree is looked up in the larger one;
+    /// trees of similar size are walked side by side in linear time.
+    pub fn is_disjoint(&self, other: &Self) -> bool {
+        self.map.is_disjoint(&other.map)
+    }
+    /// Returns true if every element of `self` is also in `other`; costs
+    /// the same as `is_disjoint`.
+    pub fn is_subset(&self, other: &Self) -> bool {
+        self.map.is_subset(&other.map)
+    }
+    /// Returns true if every element of `other` is also in `self`.
+    pub fn is_superset(&self, other: &Self) -> bool {
+        other.is_subset(self)
+    }
+    /// Returns the number of elements less than `val`, which is the position
+    /// `val` holds or would hold in order.
+    pub fn rank(&self, val: &K) -> usize {
+        self.map.rank(val)
+    }
+    /// Returns the element at position `index` in order, counting from zero.
+    pub fn select(&self, index: usize) -> Option<&K> {
+        self.map.select(index).map(|(key, _)| key)
+    }
+    /// Returns the number of elements within `range`.
+    pub fn count_range<R: RangeBounds<K>>(&self, range: R) -> usize {
+        self.map.count_range(range)
+    }
+    /// Returns the summary of the elements within `range`, assembled from
+    /// O(log n) cached summaries; see `TreeMap::aggregate`.
+    pub fn aggregate<R: RangeBounds<K>>(&self, range: R) -> S {
+        self.map.aggregate(range)
+    }
+    /// Returns the greatest element less than or equal to `val`.
+    pub fn floor(&self, val: &K) -> Option<&K> {
+        self.map.floor(val).map(|(key, _)| key)
+    }
+    /// Returns the least element greater than or equal to `val`.
+    pub fn ceiling(&self, val: &K) -> Option<&K> {
+        self.map.ceiling(val).map(|(key, _)| key)
+    }
+    /// Returns the greatest element strictly less than `val`.
+    pub fn predecessor(&self, val: &K) -> Option<&K> {
+        self.map.predecessor(val).map(|(key, _)| key)
+    }
+    /// Returns the least element strictly greater than `val`.
+    pub fn successor(&self, val: &K) -> Option<&K> {
+        self.map.successor(val).map(|(key, _)| key)
+    }
+}
+
+impl<K: Clone, C: Compare<K> + Default, S: Summary<K>> Default for Tree<K, C, S> {
+    fn default() -> Self {
+        Self::augmented(C::default())
+    }
+}
+
+impl<K: Clone, C: Compare<K> + Default, S: Summary<K>> FromIterator<K> for Tree<K, C, S> {
+    fn from_iter<I: IntoIterator<Item = K>>(iter: I) -> Self {
+        Self {
+            map: iter.into_iter().map(|key| (key, ())).collect(),
+        }
+    }
+}
+
+impl<K: Clone, C: Compare<K>, S: Summary<K>> Extend<K> for Tree<K, C, S> {
+    fn extend<I: IntoIterator<Item = K>>(&mut self, iter: I) {
+        self.map.extend(iter.into_iter().map(|key| (key, ())));
+    }
+}
+
+impl<K: Clone, C: Compare<K>, S: Summary<K>> BitOr for Tree<K, C, S> {
+    type Output = Self;
+
+    fn bitor(self, other: Self) -> Self {
+        self.into_union(other)
+    }
+}
+
+impl<K: Clone, C: Compare<K> + Clone, S: Summary<K>> BitOr for &Tree<K, C, S> {
+    type Output = Tree<K, C, S>;
+
+    fn bitor(self, other: Self) -> Tree<K, C, S> {
+        self.clone().into_union(other.clone())
+    }
+}
+
+impl<K: Clone, C: Compare<K>, S: Summary<K>> BitAnd for Tree<K, C, S> {
+    type Output = Self;
+
+    fn bitand(self, other: Self) -> Self {
+        self.into_intersection(other)
+    }
+}
+
+impl<K: Clone, C: Compare<K> + Clone, S: Summary<K>> BitAnd for &Tree<K, C, S> {
+    type Output = Tree<K, C, S>;
+
+    fn bitand(self, other: Self) -> Tree<K, C, S> {
+        Tree {
+            map: self.map.filter_shared(&other.map, true),
+        }
+    }
+}
+
+impl<K: Clone, C: Compare<K>, S: Summary<K>> Sub for Tree<K, C, S> {
+    type Output = Self;
+
+    fn sub(self, other: Self) -> Self {
+        self.into_difference(other)
+    }
+}
+
+impl<K: Clone, C: Compare<K> + Clone, S: Summary<K>> Sub for &Tree<K, C, S> {
+    type Output = Tree<K, C, S>;
+
+    fn sub(self, other: Self) -> Tree<K, C, S> {
+        Tree {
+            map: self.map.filter_shared(&other.map, false),
+        }
+    }
+}
+
+impl<K: Clone, C: Compare<K>, S: Summary<K>> BitXor for Tree<K, C, S> {
+    type Output = Self;
+
+    fn bitxor(self, other: Self) -> Self {
+        self.into_symmetric_difference(other)
+    }
+}
+
+impl<K: Clone, C: Compare<K> + Clone, S: Summary<K>> BitXor for &Tree<K, C, S> {
+    type Output = Tree<K, C, S>;
+
+    fn bitxor(self, other: Self) -> Tree<K, C, S> {
+        self.clone().into_symmetric_difference(other.clone())
+    }
+}
+
+impl<K, C, S> Collection for Tree<K, C, S> {
+    type Item = K;
+}
+
+impl<K: Clone, C: Compare<K>, S: Summary<K>> Contains for Tree<K, C, S> {
+    fn contains(&self, value: &K) -> bool {
+        self.find(value).is_some()
+    }
+}
+
+impl<K: Clone, C: Compare<K>, S: Summary<K>> Cardinality for Tree<K, C, S> {
+    type Size = usize;
+
+    fn size(&self) -> usize {
+        self.len()
+    }
+}
+
+impl<K: Clone, C: Compare<K> + Default, S: Summary<K>> Empty for Tree<K, C, S> {
+    fn empty() -> Self {
+        Self::default()
+    }
+}
+
+// The union holds every element of both trees, so copying them costs no more
+// than building it; intersection and difference work from the borrowed trees,
+// as the operators on `&Tree` do.
+//
+impl<K: Clone, C: Compare<K> + Clone, S: Summary<K>> Union for Tree<K, C, S> {
+    type Output = Self;
+
+    fn union(&self, other: &Self) -> Self {
+        self | other
+    }
+}
+
+impl<K: Clone, C: Compare<K> + Clone, S: Summary<K>> Intersection for Tree<K, C, S> {
+    type Output = Self;
+
+    fn intersection(&self, other: &Self) -> Self {
+        self & other
+    }
+}
+
+impl<K: Clone, C: Compare<K> + Clone, S: Summary<K>> Difference for Tree<K, C, S> {
+    type Output = Self;
+
+    fn difference(&self, other: &Self) -> Self {
+        self - other
+    }
+}
+
+impl<K: Clone, C: Compare<K>, S: Summary<K>> Disjoint for Tree<K, C, S> {
+    fn is_disjoint(&self, other: &Self) -> bool {
+        Tree::is_disjoint(self, other)
+    }
+}
+
+impl<K: Clone, C: Compare<K>, S: Summary<K>> Subset for Tree<K, C, S> {
+    fn is_subset(&self, other: &Self) -> bool {
+        Tree::is_subset(self, other)
+    }
+}
+
+impl<K, C, S> IntoIterator for Tree<K, C, S> {
+    type Item = K;
+    type IntoIter = IntoIter<K, S>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        IntoIter {
+            inner: self.map.into_iter(),
+        }
+    }
+}
+
+impl<'a, K: Clone, C: Compare<K>, S: Summary<K>> IntoIterator for &'a Tree<K, C, S> {
+    type Item = &'a K;
+    type IntoIter = Iter<'a, K, S>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+/// An iterator over the elements of a `Tree`, in order.
+pub struct Iter<'a, K, S = ()> {
+    inner: map::Iter<'a, K, (), S>,
+}
+
+impl<'a, K, S> Iterator for Iter<'a, K, S> {
+    type Item = &'a K;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.inner.next().map(|(key, _)| key)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.inner.size_hint()
+    }
+}
+
+impl<K, S> DoubleEndedIterator for Iter<'_, K, S> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        self.inner.next_back().map(|(key, _)| key)
+    }
+}
+
+impl<K, S> ExactSizeIterator for Iter<'_, K, S> {}
+
+impl<K, S> FusedIterator for Iter<'_, K, S> {}
+
+/// An iterator over a sub-range of the elements of a `Tree`, in order.
+pub struct Range<'a, K, S = ()> {
+    inner: map::Range<'a, K, (), S>,
+}
+
+impl<'a, K, S> Iterator for Range<'a, K, S> {
+    type Item = &'a K;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.inner.next().map(|(key, _)| key)
+    }
+}
+
+impl<K, S> DoubleEndedIterator for Range<'_, K, S> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        self.inner.next_back().map(|(key, _)| key)
+    }
+}
+
+impl<K, S> FusedIterator for Range<'_, K, S> {}
+
+/// A cursor over a `Tree`, positioned on an element or on the "ghost"
+/// position between the greatest element and the least.
+pub struct Cursor<'a, K, C, S = ()> {
+    inner: map::Cursor<'a, K, (), C, S>,
+}
+
+impl<'a, K: Clone, C: Compare<K>, S: Summary<K>> Cursor<'a, K, C, S> {
+    /// Returns the element under the cursor, or `None` at the ghost position.
+    pub fn peek(&self) -> Option<&'a K> {
+        self.inner.peek().map(|(key, _)| key)
+    }
+    /// Moves to the next element, wrapping through the ghost position.
+    pub fn move_next(&mut self) {
+        self.inner.move_next();
+    }
+    /// Moves to the previous element, wrapping through the ghost position.
+    pub fn move_prev(&mut self) {
+        self.inner.move_prev();
+    }
+    /// Moves to the least element at or after `val`, or to the ghost if
+    /// there is none.
+    pub fn seek(&mut self, val: &K) {
+        self.inner.seek(val);
+    }
+}
+
+/// A cursor over a `Tree` that can also insert and remove elements.
+pub struct CursorMut<'a, K, C, S = ()> {
+    inner: map::CursorMut<'a, K, (), C, S>,
+}
+
+impl<K: Clone, C: Compare<K>, S: Summary<K>> CursorMut<'_, K, C, S> {
+    /// Returns the element under the cursor, or `None` at the ghost position.
+    pub fn peek(&self) -> Option<&K> {
+        self.inner.peek().map(|(key, _)| key)
+    }
+    /// Moves to the next element, wrapping through the ghost position.
+    pub fn move_next(&mut self) {
+        self.inner.move_next();
+    }
+    /// Moves to the previous element, wrapping through the ghost position.
+    pub fn move_prev(&mut self) {
+        self.inner.move_prev();
+    }
+    /// Moves to the least element at or after `val`, or to the ghost if
+    /// there is none.
+    pub fn seek(&mut self, val: &K) {
+        self.inner.seek(val);
+    }
+    /// Removes the element under the cursor and moves on to the next one.
+    pub fn remove_current(&mut self) -> Option<K> {
+        self.inner.remove_current().map(|(key, _)| key)
+    }
+    /// Inserts `val` just before the cursor, which stays where it is.
+    ///
+    /// Panics unless `val` orders strictly between its would-be neighbours.
+    pub fn insert_before(&mut self, val: K) {
+        self.inner.insert_before(val, ());
+    }
+    /// Inserts `val` just after the cursor, which stays where it is.
+    ///
+    /// Panics unless `val` orders strictly between its would-be neighbours.
+    pub fn insert_after(&mut self, val: K) {
+        self.inner.insert_after(val, ());
+    }
+}
+
+/// An iterator that removes and yields the elements of a `Tree` within a
+/// range that match a predicate, returned by `Tree::extract_if`.
+pub struct ExtractIf<'a, K: Clone, C: Compare<K>, S: Summary<K>, F> {
+    extract: map::Extract<'a, K, (), C, S>,
+    pred: F,
+}
+
+impl<K: Clone, C: Compare<K>, S: Summary<K>, F: FnMut(&K) -> bool> Iterator
+    for ExtractIf<'_, K, C, S, F>
+{
+    type Item = K;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let pred = &mut self.pred;
+        self.extract
+            .next_with(&mut |key: &K, _: &mut ()| pred(key))
+            .map(|(key, _)| key)
+    }
+}
+
+impl<K: Clone, C: Compare<K>, S: Summary<K>, F: FnMut(&K) -> bool> FusedIterator
+    for ExtractIf<'_, K, C, S, F>
+{
+}
+
+/// An owning iterator over the elements of a `Tree`, in order.
+pub struct IntoIter<K, S = ()> {
+    inner: map::IntoIter<K, (), S>,
+}
+
+impl<K, S> Iterator for IntoIter<K, S> {
+    type Item = K;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.inner.next().map(|(key, _)| key)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.inner.size_hint()
+    }
+}
+
+impl<K, S> DoubleEndedIterator for IntoIter<K, S> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        self.inner.next_back().map(|(key, _)| key)
+    }
+}
+
+impl<K, S> ExactSizeIterator for IntoIter<K, S> {}
+
+impl<K, S> FusedIterator for IntoIter<K, S> {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn insert_test() {
+        assert_eq!(2 + 2, 4);
+
+        let mut t = Tree::new();
+
+        assert!(t.find(&10).is_none());
+
+        t.insert(10);
+        println!("{:#?}", t);
+
+        assert_eq!(t.find(&10), Some(&10));
+
+        for k in 0..1000 {
+            t.insert(k);
+        }
+
+        for k in 0..1000 {
+            assert!(t.find(&k) == Some(&k));
+        }
+
+        assert_eq!(t.height(), 10);
+
+        for k in 1000..100000 {
+            t.insert(k);
+        }
+
+        for k in 1000..100000 {
+            assert!(t.find(&k) == Some(&k));
+        }
+
+        assert_eq!(t.height(), 17);
+    }
+
+    #[test]
+    fn remove_test() {
+        let mut t = Tree::new();
+
+        for k in 0..100000 {
+            t.insert(k);
+        }
+
+        for k in 0..100000 {
+            assert!(t.find(&k) == Some(&k));
+        }
+
+        assert_eq!(t.height(), 17);
+
+        for k in 0..100000 {
+            assert!(t.find(&k) == Some(&k));
+            assert_eq!(t.remove(&k), Some(k));
+            assert!(t.find(&k).is_none(), "k={}, tree={:#?}", k, t);
+            assert_eq!(t.remove(&k), None);
+        }
+
+        for k in 0..100000 {
+            assert!(t.find(&k).is_none());
+        }
+
+        assert_eq!(t.height(), 0);
+    }
+
+    #[test]
+    fn duplicate_insert_test() {
+        // Covers every shape from a single Leaf2 up to several levels of
+        // Inner2/Inner3 nodes.
+        for n in 1..200 {
+            let mut t = Tree::new();
+
+            for k in 0..n {
+                assert!(t.insert(k));
+            }
+
+            let height = t.height();
+
+            for k in 0..n {
+                assert!(!t.insert(k), "n={}, k={}", n, k);
+            }
+
+            assert_eq!(t.height(), height);
+            assert_eq!(t.len(), n);
+
+            for k in 0..n {
+                assert_eq!(t.remove(&k), Some(k));
+                assert!(t.find(&k).is_none(), "n={}, k={}", n, k);
+            }
+
+            assert!(t.is_empty());
+            assert_eq!(t.height(), 0);
+        }
+    }
+
+    #[test]
+    fn comparator_test() {
+        // f64 is not Ord; order it with total_cmp instead.
+        let mut t = Tree::with_comparator(|a: &f64, b: &f64| a.total_cmp(b));
+
+        for k in 0..1000 {
+            assert!(t.insert(k as f64 / 4.0));
+        }
+
+        for k in 0..1000 {
+            assert_eq!(t.find(&(k as f64 / 4.0)), Some(&(k as f64 / 4.0)));
+        }
+
+        assert!(t.find(&0.1).is_none());
+
+        let mut t = Tree::with_comparator(|a: &i32, b: &i32| b.cmp(a));
+
+        for k in 0..1000 {
+            t.insert(k);
+        }
+
+        for k in 0..1000 {
+            assert_eq!(t.remove(&k), Some(k));
+            assert!(t.find(&k).is_none());
+        }
+
+        assert_eq!(t.height(), 0);
+
+        let mut m = TreeMap::with_comparator(|a: &String, b: &String| {
+            a.to_lowercase().cmp(&b.to_lowercase())
+        });
+
+        m.insert("Apple".to_string(), 1);
+        assert_eq!(m.insert("APPLE".to_string(), 2), Some(1));
+        assert_eq!(m.get(&"apple".to_string()), Some(&2));
+    }
+
+    #[test]
+    fn iter_test() {
+        let mut t = Tree::new();
+
+        for k in 0..10000 {
+            t.insert((k * 7919) % 10000);
+        }
+
+        assert!(t.iter().copied().eq(0..10000));
+        assert!(t.iter().rev().copied().eq((0..10000).rev()));
+        assert_eq!(t.iter().size_hint(), (10000, Some(10000)));
+
+        let mut n = 0;
+        for (i, k) in (&t).into_iter().enumerate() {
+            assert_eq!(*k, i);
+            n += 1;
+        }
+        assert_eq!(n, 10000);
+
+        let t: Tree<i32> = Tree::new();
+        assert_eq!(t.iter().next(), None);
+        assert_eq!(t.iter().next_back(), None);
+    }
+
+    #[test]
+    fn range_test() {
+        use std::ops::Bound::{Excluded, Included};
+
+        let mut t = Tree::new();
+
+        for k in 0..100000 {
+            t.insert(k);
+        }
+
+        assert!(t.range(500..600).copied().eq(500..600));
+        assert!(t.range(99990..).rev().copied().eq((99990..100000).rev()));
+        assert!(t.range(..=3).copied().eq(0..=3));
+        assert_eq!(t.range(100000..).next(), None);
+
+        // Bounds follow the tree's own order, which is reversed here.
+        let mut t = Tree::with_comparator(|a: &i32, b: &i32| b.cmp(a));
+        for k in 0..100 {
+            t.insert(k);
+        }
+        assert!(t
+            .range((Included(60), Excluded(50)))
+            .copied()
+            .eq((51..=60).rev()));
+    }
+
+    #[test]
+    fn neighbor_test() {
+        let mut t = Tree::new();
+
+        assert_eq!(t.floor(&0), None);
+        assert_eq!(t.successor(&0), None);
+
+        for k in 0..1000 {
+            t.insert(k * 3);
+        }
+
+        for k in -5..3005 {
+            let floor = (k >= 0).then(|| k.min(2997) / 3 * 3);
+            let ceiling = (k <= 2997).then(|| (k.max(0) + 2) / 3 * 3);
+            let predecessor = (k > 0).then(|| (k.min(2998) - 1) / 3 * 3);
+            let successor = (k < 2997).then(|| (k.max(-1) + 3) / 3 * 3);
+
+            assert_eq!(t.floor(&k).copied(), floor, "k={}", k);
+            assert_eq!(t.ceiling(&k).copied(), ceiling, "k={}", k);
+            assert_eq!(t.predecessor(&k).copied(), predecessor, "k={}", k);
+            assert_eq!(t.successor(&k).copied(), successor, "k={}", k);
+        }
+
+        // Separators left behind by removals must not mislead the descent.
+        for k in (0..1000).filter(|k| k % 4 != 0) {
+            t.remove(&(k * 3));
+        }
+
+        for k in 0..2997 {
+            let expected = t.range(..=k).next_back().copied();
+            assert_eq!(t.floor(&k).copied(), expected, "k={}", k);
+            let expected = t.range(k + 1..).next().copied();
+            assert_eq!(t.successor(&k).copied(), expected, "k={}", k);
+        }
+    }
+
+    #[test]
+    fn first_last_test() {
+        let mut t = Tree::new();
+
+        assert_eq!(t.first(), None);
+        assert_eq!(t.pop_last(), None);
+
+        for k in 0..10000 {
+            t.insert((k * 7919) % 10000);
+        }
+
+        for k in 0..5000 {
+            assert_eq!(t.first(), Some(&k));
+            assert_eq!(t.last(), Some(&(9999 - k)));
+            assert_eq!(t.pop_first(), Some(k));
+            assert_eq!(t.pop_last(), Some(9999 - k));
+            assert_eq!(t.len(), 9998 - 2 * k);
+        }
+
+        assert_eq!(t.pop_first(), None);
+        assert_eq!(t.height(), 0);
+    }
+
+    #[test]
+    fn cursor_test() {
+        let mut t = Tree::new();
+
+        for k in 0..10000 {
+            t.insert(k);
+        }
+
+        // Delete every key in [2000, 8000) that is not a multiple of 7.
+        let mut cursor = t.cursor_mut();
+        cursor.seek(&2000);
+        while let Some(k) = cursor.peek() {
+            if *k >= 8000 {
+                break;
+            }
+            if k % 7 == 0 {
+                cursor.move_next();
+            } else {
+                cursor.remove_current();
+            }
+        }
+        cursor.move_prev();
+        assert_eq!(cursor.peek(), Some(&7994));
+        cursor.insert_after(7995);
+
+        let expected = (0..2000)
+            .chain((2000..8000).filter(|k| k % 7 == 0))
+            .chain(std::iter::once(7995))
+            .chain(8000..10000);
+        assert!(t.iter().copied().eq(expected));
+
+        let mut cursor = t.cursor();
+        cursor.seek(&7990);
+        cursor.move_next();
+        assert_eq!(cursor.peek(), Some(&7995));
+    }
+
+    #[test]
+    fn from_sorted_test() {
+        for n in 0..300 {
+            let t = Tree::from_sorted(0..n);
+            assert_eq!(t.len(), n);
+            t.map.check();
+            assert!(t.iter().copied().eq(0..n));
+        }
+
+        let mut t = Tree::from_sorted(0..100000);
+        t.map.check();
+        assert!(t.height() <= 17);
+
+        for k in 0..100000 {
+            assert_eq!(t.find(&k), Some(&k));
+        }
+        for k in (0..100000).step_by(3) {
+            assert_eq!(t.remove(&k), Some(k));
+        }
+        for k in 100000..101000 {
+            assert!(t.insert(k));
+        }
+        t.map.check();
+        assert_eq!(t.len(), 66666 + 1000);
+    }
+
+    #[test]
+    #[should_panic]
+    fn from_sorted_unsorted_test() {
+        Tree::from_sorted(vec![1, 3, 2]);
+    }
+
+    #[test]
+    fn from_iter_test() {
+        let t: Tree<i32> = (0..10000).map(|k| (k * 7919) % 5000).collect();
+        assert_eq!(t.len(), 5000);
+        assert!(t.iter().copied().eq(0..5000));
+        t.map.check();
+
+        let mut t: Tree<i32> = (0..1000).collect();
+        t.extend(vec![5, 2000, 1500, 1500]);
+        t.extend((500..3000).rev());
+        assert!(t.iter().copied().eq(0..3000));
+        t.map.check();
+    }
+
+    #[test]
+    fn split_off_test() {
+        for n in [0, 1, 2, 3, 10, 100, 1000] {
+            for at in [-1, 0, 1, n / 3, n / 2, n - 1, n, n + 1] {
+                let mut t: Tree<i32> = (0..n).filter(|k| k % 7 != 3).collect();
+                let mut upper = t.split_off(&at);
+                t.map.check();
+                upper.map.check();
+                assert!(t
+                    .iter()
+                    .copied()
+                    .eq((0..n.min(at).max(0)).filter(|k| k % 7 != 3)));
+                assert!(upper
+                    .iter()
+                    .copied()
+                    .eq((at.max(0)..n).filter(|k| k % 7 != 3)));
+
+                t.append(&mut upper);
+                t.map.check();
+                assert!(upper.is_empty());
+                assert!(t.iter().copied().eq((0..n).filter(|k| k % 7 != 3)));
+            }
+        }
+    }
+
+    #[test]
+    fn append_test() {
+        for n in [0, 1, 2, 5, 30, 500] {
+            for m in [0, 1, 2, 5, 30, 500] {
+                let mut t = Tree::from_sorted(0..n);
+                let mut other = Tree::from_sorted(n..n + m);
+                t.append(&mut other);
+                t.map.check();
+                assert!(t.iter().copied().eq(0..n + m));
+            }
+        }
+
+        let mut t = Tree::new();
+        for k in 0..1000 {
+            t.append(&mut Tree::from_sorted(vec![2 * k, 2 * k + 1]));
+        }
+        t.map.check();
+        assert!(t.iter().copied().eq(0..2000));
+    }
+
+    #[test]
+    #[should_panic]
+    fn append_overlap_test() {
+        Tree::from_sorted(0..10).append(&mut Tree::from_sorted(9..20));
+    }
+
+    #[test]
+    fn set_ops_test() {
+        use std::collections::BTreeSet;
+
+        let sizes = [
+            (0, 0),
+            (0, 50),
+            (1, 1),
+            (40, 60),
+            (5, 3000),
+            (3000, 2),
+            (700, 900),
+        ];
+        for &(n, m) in &sizes {
+            let a: BTreeSet<u32> = (0..n).map(|k| k * 3).collect();
+            let b: BTreeSet<u32> = (0..m).map(|k| k * 5 % 4099).collect();
+            let ta: Tree<u32> = a.iter().copied().collect();
+            let tb: Tree<u32> = b.iter().copied().collect();
+
+            let results = [
+                (&ta | &tb, a.union(&b).copied().collect::<Vec<_>>()),
+                (&ta & &tb, a.intersection(&b).copied().collect()),
+                (&ta - &tb, a.difference(&b).copied().collect()),
+                (&tb - &ta, b.difference(&a).copied().collect()),
+                (&ta ^ &tb, a.symmetric_difference(&b).copied().collect()),
+            ];
+            for (t, expected) in &results {
+                t.map.check();
+                assert!(t.iter().eq(expected.iter()));
+            }
+        }
+    }
+
+    #[test]
+    fn union_keeps_self_test() {
+        let by_len = |a: &&str, b: &&str| a.len().cmp(&b.len());
+        let mut a = Tree::with_comparator(by_len);
+        let mut b = Tree::with_comparator(by_len);
+        a.insert("a");
+        a.insert("ccc");
+        b.insert("b");
+        b.insert("dd");
+        let u = a.into_union(b);
+        assert!(u.iter().copied().eq(vec!["a", "dd", "ccc"]));
+
+        // Large enough to take the split/join path in both directions.
+        //
+        let by_key = |a: &(u32, bool), b: &(u32, bool)| a.0.cmp(&b.0);
+        let big = || Tree::from_sorted_with_comparator((0..2000).map(|k| (k, true)), by_key);
+        let small = || Tree::from_sorted_with_comparator((0..4).map(|k| (k * 500, false)), by_key);
+        assert!(big()
+            .into_union(small())
+            .iter()
+            .all(|&(_, from_big)| from_big));
+        assert!(small()
+            .into_union(big())
+            .iter()
+            .all(|&(k, from_big)| from_big == (k % 500 != 0)));
+        assert!(small()
+            .into_intersection(big())
+            .iter()
+            .all(|&(_, from_big)| !from_big));
+    }
+
+    #[test]
+    fn gcollections_test() {
+        use gcollections::ops::IsEmpty;
+        use std::collections::BTreeSet;
+
+        // Only the traits, as code generic over collections would see them.
+        fn results<T>(a: &T, b: &T) -> (T, T, T, [bool; 3])
+        where
+            T: Union<Output = T> + Intersection<Output = T> + Difference<Output = T>,
+            T: Disjoint + Subset,
+        {
+            let tests = [a.is_disjoint(b), a.is_subset(b), b.is_subset(a)];
+            (a.union(b), a.intersection(b), a.difference(b), tests)
+        }
+
+        let evens = |n: u32| (0..n).map(|k| k * 2);
+        let odds = |n: u32| (0..n).map(|k| k * 2 + 1);
+        let cases: Vec<(BTreeSet<u32>, BTreeSet<u32>)> = vec![
+            (BTreeSet::new(), (0..10).collect()),
+            (
+                (0..500).map(|k| k * 3).collect(),
+                (0..700).map(|k| k * 5).collect(),
+            ),
+            // Subsets and disjoint pairs, both looked up and walked.
+            ((0..3).map(|k| k * 600).collect(), (0..3000).collect()),
+            ((0..3000).collect(), (0..3).map(|k| k * 600).collect()),
+            (evens(1500).collect(), (0..3000).collect()),
+            (evens(4).collect(), odds(1500).collect()),
+            (evens(1500).collect(), odds(1500).collect()),
+            (evens(1500).collect(), evens(1499).chain(Some(5)).collect()),
+        ];
+        for (a, b) in &cases {
+            let ta: Tree<u32> = a.iter().copied().collect();
+            let tb: Tree<u32> = b.iter().copied().collect();
+
+            let (union, intersection, difference, tests) = results(&ta, &tb);
+            assert!(union.iter().eq(a.union(b)));
+            assert!(intersection.iter().eq(a.intersection(b)));
+            assert!(difference.iter().eq(a.difference(b)));
+            assert_eq!(tests, [a.is_disjoint(b), a.is_subset(b), b.is_subset(a)]);
+            assert_eq!(ta.is_superset(&tb), a.is_superset(b));
+
+            assert_eq!(Cardinality::size(&ta), a.len());
+            assert!(a.iter().all(|k| Contains::contains(&ta, k)));
+            assert!(!Contains::contains(&ta, &1_000_000));
+        }
+
+        // Small against large: only the few elements of the result are
+        // copied, never the large tree.
+        #[derive(Debug, PartialEq, Eq, PartialOrd, Ord)]
+        struct Tracked(u32);
+
+        thread_local! {
+            static CLONES: std::cell::Cell<usize> = const { std::cell::Cell::new(0) };
+        }
+
+        impl Clone for Tracked {
+            fn clone(&self) -> Self {
+                CLONES.with(|n| n.set(n.get() + 1));
+                Tracked(self.0)
+            }
+        }
+
+        let big: Tree<Tracked> = Tree::from_sorted((0..100_000).map(Tracked));
+        let small: Tree<Tracked> = Tree::from_sorted((0..5).map(|k| Tracked(k * 1000 + 1)));
+        CLONES.with(|n| n.set(0));
+        let both = small.intersection(&big);
+        let reversed = big.intersection(&small);
+        let neither = small.difference(&big);
+        let by_operator = (&small & &big, &big & &small, &small - &big);
+        assert!(CLONES.with(|n| n.get()) < 100);
+        assert!(both.iter().eq(small.iter()));
+        assert!(reversed.iter().eq(small.iter()));
+        assert!(neither.is_empty());
+        assert!(by_operator.0.iter().eq(small.iter()));
+        assert!(by_operator.1.iter().eq(small.iter()));
+        assert!(by_operator.2.is_empty());
+        assert_eq!(small.union(&big).len(), 100_000);
+
+        let empty: Tree<u32> = Empty::empty();
+        assert!(IsEmpty::is_empty(&empty));
+        assert!(empty.is_subset(&Tree::from_sorted(0..10)));
+    }
+
+    #[test]
+    fn retain_test() {
+        let mut t: Tree<u32> = (0..5000).collect();
+        let mut visited = Vec::new();
+        t.retain(|k| {
+            visited.push(*k);
+            k % 10 == 0
+        });
+        assert!(visited.into_iter().eq(0..5000));
+        t.map.check();
+        assert!(t.iter().copied().eq((0..5000).step_by(10)));
+
+        t.retain(|k| k % 20 != 0);
+        t.map.check();
+        assert!(t.iter().copied().eq((10..5000).step_by(20)));
+
+        let removed: Vec<_> = t.extract_if(1000..=2010, |_| true).collect();
+        assert!(removed.into_iter().eq((1010..=2010).step_by(20)));
+        t.map.check();
+        assert_eq!(t.len(), 250 - 51);
+
+        t.retain(|_| false);
+        t.map.check();
+        assert!(t.is_empty());
+    }
+
+    #[test]
+    fn remove_range_test() {
+        for n in [0, 1, 2, 3, 7, 50, 400] {
+            for a in 0..n.min(12) + 1 {
+                for b in [a, a + 1, a + 5, n / 2, n] {
+                    if b < a {
+                        continue;
+                    }
+                    let mut t = Tree::from_sorted(0..n);
+                    assert_eq!(t.remove_range(a..b), b.min(n) - a.min(n));
+                    t.map.check();
+                    assert!(t.iter().copied().eq((0..a).chain(b..n)));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn rank_select_test() {
+        let mut t = Tree::new();
+        for k in (0..3000).rev() {
+            t.insert(k * 3 % 3001);
+        }
+        for i in 0..3000 {
+            let k = *t.select(i).unwrap();
+            assert_eq!(t.rank(&k), i);
+            assert_eq!(t.count_range(..k), i);
+        }
+        assert_eq!(t.count_range(100..200), 100);
+        t.remove_range(100..200);
+        t.retain(|k| k % 2 == 0);
+        let mut upper = t.split_off(&2000);
+        assert_eq!(t.count_range(..), t.len());
+        assert_eq!(upper.select(0), Some(&2000));
+        t.append(&mut upper);
+        t.map.check();
+        assert_eq!(t.rank(&2000), 950);
+    }
+
+    // Records every key in order, so that a stale or misordered cached summary
+    // shows up as a wrong sequence.
+    //
+    #[derive(Clone, Debug, PartialEq)]
+    struct Seq(Vec<u32>);
+
+    impl Summary<u32> for Seq {
+        fn empty() -> Self {
+            Seq(Vec::new())
+        }
+        fn of(key: &u32) -> Self {
+            Seq(vec![*key])
+        }
+        fn combine(&self, other: &Self) -> Self {
+            Seq(self.0.iter().chain(&other.0).copied().collect())
+        }
+    }
+
+    fn check_aggregates(t: &Tree<u32, Natural, Seq>) {
+        let max = t.last().map_or(0, |k| k + 2);
+        for a in (0..max).step_by(7) {
+            for b in (a..max).step_by(13) {
+                let expected: Vec<u32> = t.range(a..b).copied().collect();
+                assert_eq!(t.aggregate(a..b), Seq(expected));
+            }
+        }
+        assert_eq!(t.aggregate(..), Seq(t.iter().copied().collect()));
+    }
+
+    #[test]
+    fn aggregate_test() {
+        let mut t: Tree<u32, Natural, Seq> = Tree::default();
+        for k in 0..300 {
+            t.insert(k * 7 % 300);
+        }
+        check_aggregates(&t);
+        for k in (0..300).step_by(4) {
+            t.remove(&k);
+        }
+        t.pop_first();
+        check_aggregates(&t);
+
+        let mut upper = t.split_off(&150);
+        check_aggregates(&t);
+        check_aggregates(&upper);
+        upper.remove_range(200..220);
+        upper.retain(|k| k % 5 != 0);
+        t.append(&mut upper);
+        check_aggregates(&t);
+
+        let other: Tree<u32, Natural, Seq> = (100..400).step_by(3).collect();
+        check_aggregates(&(&t | &other));
+        check_aggregates(&(&t - &other));
+        let small: Tree<u32, Natural, Seq> = (0..4).map(|k| k * 90).collect();
+        check_aggregates(&(t.clone() ^ small));
+
+        let mut cursor = t.cursor_mut();
+        cursor.seek(&100);
+        cursor.remove_current();
+        cursor.insert_before(100);
+        check_aggregates(&t);
+    }
+
+    thread_local! {
+        static SUMMARIES: std::cell::Cell<usize> = const { std::cell::Cell::new(0) };
+    }
+
+    // Counts every summary it makes, to show how much of the tree an
+    // aggregate visits.
+    //
+    #[derive(Clone, Debug, PartialEq)]
+    struct Counted(u64);
+
+    impl Summary<u32> for Counted {
+        fn empty() -> Self {
+            SUMMARIES.with(|n| n.set(n.get() + 1));
+            Counted(0)
+        }
+        fn of(key: &u32) -> Self {
+            SUMMARIES.with(|n| n.set(n.get() + 1));
+            Counted(*key as u64)
+        }
+        fn combine(&self, other: &Self) -> Self {
+            Counted(self.0 + other.0)
+        }
+    }
+
+    #[test]
+    fn aggregate_cost_test() {
+        for &n in &[1_000u32, 10_000, 100_000] {
+            let t: Tree<u32, Natural, Counted> = Tree::from_iter(0..n);
+            let height = t.height();
+            for (start, end) in [(n / 2, n / 2 + 10), (0, 1), (n - 1, n), (3, n - 3)] {
+                SUMMARIES.with(|n| n.set(0));
+                let sum = t.aggregate(start..end);
+                assert_eq!(sum, Counted((start..end).map(u64::from).sum()));
+                let made = SUMMARIES.with(|n| n.get());
+                assert!(made <= 12 * height, "n={} made={}", n, made);
+            }
+        }
+    }
+
+    #[test]
+    fn into_iter_test() {
+        let mut t = Tree::new();
+
+        for k in 0..100000 {
+            t.insert(k);
+        }
+
+        let mut expected = 0..100000;
+        let mut iter = t.into_iter();
+        while let Some(k) = iter.next() {
+            assert_eq!(Some(k), expected.next());
+            assert_eq!(iter.next_back(), expected.next_back());
+            assert_eq!(iter.len(), expected.len());
+        }
+        assert_eq!(expected.next(), None);
+    }
+
+    #[test]
+    fn generic_key_test() {
+        let mut t = Tree::new();
+
+        for k in 0..1000u64 {
+            t.insert(format!("{:05}", k));
+        }
+
+        for k in 0..1000u64 {
+            assert_eq!(t.find(&format!("{:05}", k)), Some(&format!("{:05}", k)));
+        }
+
+        assert!(t.find(&"x".to_string()).is_none());
+
+        let mut t = Tree::new();
+
+        for k in 0..100 {
+            t.insert((k % 10, k / 10));
+        }
+
+        for k in 0..100 {
+            assert_eq!(t.find(&(k % 10, k / 10)), Some(&(k % 10, k / 10)));
+        }
+    }
+
+    #[test]
+    fn remove_scattered_test() {
+        let mut t = Tree::new();
+
+        for k in 0..10000 {
+            t.insert(k);
+        }
+
+        for i in 0..10000 {
+            let k = (i * 7919) % 10000;
+            assert_eq!(t.remove(&k), Some(k));
+            assert!(t.find(&k).is_none());
+            assert_eq!(t.len(), 9999 - i);
+            if i % 97 == 0 {
+                for j in (i + 1)..10000 {
+                    let k = (j * 7919) % 10000;
+                    assert_eq!(t.find(&k), Some(&k), "k={}", k);
+                }
+            }
         }
 
         assert_eq!(t.height(), 0);
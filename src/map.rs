@@ -0,0 +1,1354 @@
+//! An ordered map backed by a 2-3 tree, and its iterator types.
+
+use std::cmp::Ordering;
+use std::iter::{FromIterator, FusedIterator};
+use std::ops::Bound::{Excluded, Included, Unbounded};
+use std::ops::RangeBounds;
+
+use crate::build;
+use crate::compare::{Compare, Natural};
+use crate::insert::InsertResult;
+use crate::iter::{check_range, Entries, IntoEntries, RangeEntries};
+use crate::join::{self, Part};
+use crate::node::Node;
+use crate::remove::{RemoveEvent, RemoveResult, Target};
+use crate::setops::{self, SetOp};
+use crate::summary::Summary;
+
+mod cursor;
+pub use self::cursor::{Cursor, CursorMut};
+
+mod entry;
+pub use self::entry::{Entry, OccupiedEntry, VacantEntry};
+
+mod extract;
+pub(crate) use self::extract::Extract;
+pub use self::extract::ExtractIf;
+
+use Node::Nil;
+
+/// An ordered map backed by a 2-3 tree.
+///
+/// Values live next to their keys in the leaves; the separators in the inner
+/// nodes are keys only, so inner nodes stay small.  Keys are ordered by the
+/// comparator `C`, which defaults to the key type's own `Ord`.
+#[derive(Clone, Debug)]
+pub struct TreeMap<K, V, C = Natural, S = ()> {
+    root: Box<Node<K, V, S>>,
+    len: usize,
+    cmp: C,
+    remove_hook: Option<fn(RemoveEvent)>,
+}
+
+impl<K: Ord + Clone, V> TreeMap<K, V> {
+    /// Creates an empty map.
+    pub fn new() -> Self {
+        Self::with_comparator(Natural)
+    }
+
+    /// Builds a map in linear time from entries in strictly ascending key
+    /// order.
+    ///
+    /// Panics if the keys are not strictly ascending.
+    pub fn from_sorted<I: IntoIterator<Item = (K, V)>>(iter: I) -> Self {
+        Self::from_sorted_with_comparator(iter, Natural)
+    }
+}
+
+impl<K: Clone, V, C: Compare<K>> TreeMap<K, V, C> {
+    /// Creates an empty map ordered by `cmp`.
+    pub fn with_comparator(cmp: C) -> Self {
+        Self::augmented(cmp)
+    }
+
+    /// Builds a map ordered by `cmp`, in linear time, from entries in
+    /// strictly ascending key order under `cmp`.
+    ///
+    /// Panics if the keys are not strictly ascending.
+    pub fn from_sorted_with_comparator<I: IntoIterator<Item = (K, V)>>(iter: I, cmp: C) -> Self {
+        Self::build(iter, cmp)
+    }
+}
+
+impl<K: Clone, V, C: Compare<K>, S: Summary<K>> TreeMap<K, V, C, S> {
+    /// Creates an empty map ordered by `cmp` whose inner nodes cache a
+    /// summary of type `S` of their keys; see `aggregate`.
+    pub fn augmented(cmp: C) -> Self {
+        Self {
+            root: Box::new(Nil),
+            len: 0,
+            cmp,
+            remove_hook: None,
+        }
+    }
+
+    // Builds a map from entries in strictly ascending key order.
+    //
+    fn build<I: IntoIterator<Item = (K, V)>>(iter: I, cmp: C) -> Self {
+        let (root, len) = build::from_sorted(iter, &cmp);
+        Self {
+            root,
+            len,
+            cmp,
+            remove_hook: None,
+        }
+    }
+
+    /// Inserts `value` under `key`.
+    ///
+    /// Returns the previous value if `key` was already present; the key itself
+    /// is not updated in that case.
+    pub fn insert(&mut self, key: K, value: V) -> Option<V> {
+        match self.root.insert((key, value), &self.cmp) {
+            InsertResult::Ok => {
+                self.len += 1;
+                None
+            }
+            InsertResult::Replaced(old) => Some(old),
+            InsertResult::Split(split_min, split) => {
+                let tmp = std::mem::replace(&mut self.root, Box::new(Nil));
+                *self.root = Node::inner2(tmp, split_min, split);
+                self.len += 1;
+                None
+            }
+        }
+    }
+
+    /// Gets the entry for `key`, for in-place insertion or update.
+    ///
+    /// The lookup records the path it took, so inserting into a vacant entry
+    /// costs no further key comparisons.
+    pub fn entry(&mut self, key: K) -> Entry<'_, K, V, C, S> {
+        Entry::new(self, key)
+    }
+
+    // Inserts an entry whose key is known to be absent at `path` (as recorded
+    // by `Node::search`), returning the path to it in its final position.
+    //
+    fn insert_at(&mut self, path: &[usize], new_val: (K, V)) -> Vec<usize> {
+        let mut loc = Vec::with_capacity(path.len() + 1);
+        let (result, in_split) = self.root.insert_at(path, new_val, &mut loc);
+        if let InsertResult::Split(split_min, split) = result {
+            let tmp = std::mem::replace(&mut self.root, Box::new(Nil));
+            *self.root = Node::inner2(tmp, split_min, split);
+            loc.push(in_split as usize);
+        }
+        self.len += 1;
+        loc.reverse();
+        loc
+    }
+
+    // Removes the entry at `path` without comparing keys, returning it along
+    // with the path to the entry that followed it, or an empty path if it
+    // was the last.  However the removal rebalanced the tree, the follower
+    // now holds the removed entry's position in key order, so its new path
+    // is found from the cached sizes.
+    //
+    fn remove_at(&mut self, path: &[usize]) -> ((K, V), Vec<usize>) {
+        let rank = self.root.rank_at(path);
+        let removed = self
+            .remove_target(Target::Path(path))
+            .expect("path leads to an entry");
+        let mut next = Vec::with_capacity(path.len());
+        if rank < self.len {
+            self.root.select_path(rank, &mut next);
+        }
+        (removed, next)
+    }
+
+    /// Removes `key` from the map, returning the removed entry.
+    pub fn remove(&mut self, key: &K) -> Option<(K, V)> {
+        self.remove_target(Target::Key(key))
+    }
+
+    /// Removes and returns the entry with the least key.
+    pub fn pop_first(&mut self) -> Option<(K, V)> {
+        self.remove_target(Target::First)
+    }
+
+    /// Removes and returns the entry with the greatest key.
+    pub fn pop_last(&mut self) -> Option<(K, V)> {
+        self.remove_target(Target::Last)
+    }
+
+    /// Returns an iterator that removes and yields the entries within
+    /// `range` for which `pred` returns true.
+    ///
+    /// The range is detached from the tree when the iterator is created, and
+    /// the entries left behind are rebuilt into it in a single pass when the
+    /// iterator is dropped, so the cost is O(log n) plus the size of the
+    /// range however many entries are removed.
+    ///
+    /// Panics if the start of the range is greater than its end, or if the
+    /// two are equal and both excluded.
+    pub fn extract_if<R, F>(&mut self, range: R, pred: F) -> ExtractIf<'_, K, V, C, S, F>
+    where
+        R: RangeBounds<K>,
+        F: FnMut(&K, &mut V) -> bool,
+    {
+        ExtractIf::new(self, range, pred)
+    }
+
+    /// Removes every entry whose key falls within `range`, returning how many
+    /// were removed.
+    ///
+    /// Subtrees lying wholly inside the range are detached without being
+    /// visited and only the two boundary paths are rebalanced, so the cost is
+    /// O(log n) plus the time to free the removed entries.
+    ///
+    /// Panics if the start of the range is greater than its end, or if the
+    /// two are equal and both excluded.
+    pub fn remove_range<R: RangeBounds<K>>(&mut self, range: R) -> usize {
+        let (lower, upper) = (range.start_bound(), range.end_bound());
+        check_range(lower, upper, &self.cmp);
+        let root = std::mem::replace(&mut self.root, Box::new(Nil));
+        let height = root.height();
+        let [below, within, above] = join::split_range(*root, height, lower, upper, &self.cmp);
+
+        let removed = within.root.size();
+        self.root = join::concat(below, above).root;
+        self.len -= removed;
+        removed
+    }
+
+    /// Keeps only the entries for which `f` returns true, visiting them in
+    /// key order.
+    pub fn retain<F: FnMut(&K, &mut V) -> bool>(&mut self, mut f: F) {
+        self.extract_if(.., |key, value| !f(key, value))
+            .for_each(drop);
+    }
+
+    /// Splits the map in two at `key`, returning the entries whose keys are
+    /// greater than or equal to it and keeping the rest, in O(log n).
+    pub fn split_off(&mut self, key: &K) -> Self
+    where
+        C: Clone,
+    {
+        let root = std::mem::replace(&mut self.root, Box::new(Nil));
+        let height = root.height();
+        let (lower, upper) = join::split(*root, height, key, &self.cmp);
+        let split_off = Self {
+            len: upper.root.size(),
+            root: upper.root,
+            cmp: self.cmp.clone(),
+            remove_hook: None,
+        };
+        self.len = lower.root.size();
+        self.root = lower.root;
+        split_off
+    }
+
+    /// Moves every entry of `other` into this map in O(log n), leaving
+    /// `other` empty.
+    ///
+    /// Panics unless every key in `other` is greater than every key in this
+    /// map.
+    pub fn append(&mut self, other: &mut Self) {
+        let sep = match (self.root.last(), other.root.first()) {
+            (_, None) => return,
+            (None, Some(_)) => {
+                std::mem::swap(&mut self.root, &mut other.root);
+                std::mem::swap(&mut self.len, &mut other.len);
+                return;
+            }
+            (Some(last), Some(first)) => {
+                assert!(
+                    self.cmp.compare(&last.0, &first.0).is_lt(),
+                    "append requires every appended key to be greater than the existing ones"
+                );
+                first.0.clone()
+            }
+        };
+        let left = std::mem::replace(&mut self.root, Box::new(Nil));
+        let right = std::mem::replace(&mut other.root, Box::new(Nil));
+        let left_height = left.height();
+        let right_height = right.height();
+        let joined = join::join(
+            Part::new(left, left_height),
+            sep,
+            Part::new(right, right_height),
+        );
+        self.root = joined.root;
+        self.len += std::mem::take(&mut other.len);
+    }
+
+    // Combines this map with `other` under `op`, keeping this map's entry
+    // where a key is in both.  Operands of similar size are merged in linear
+    // time; otherwise the smaller is threaded through the larger by split
+    // and join.
+    //
+    pub(crate) fn combine(self, other: Self, op: SetOp) -> Self {
+        let (left_len, right_len) = (self.len, other.len);
+        let left_height = self.root.height();
+        let right_height = other.root.height();
+        let (small_len, big_len, big_height) = if left_len <= right_len {
+            (left_len, right_len, right_height)
+        } else {
+            (right_len, left_len, left_height)
+        };
+
+        let (root, len) = if small_len.saturating_mul(big_height) < big_len {
+            let left = Part::new(self.root, left_height);
+            let right = Part::new(other.root, right_height);
+            let mut shared = 0;
+            let part = if left_len <= right_len {
+                setops::combine(right, left, true, op, &mut shared, &self.cmp)
+            } else {
+                setops::combine(left, right, false, op, &mut shared, &self.cmp)
+            };
+            (part.root, op.len(left_len, right_len, shared))
+        } else {
+            let left = IntoEntries::new(self.root, left_len);
+            let right = IntoEntries::new(other.root, right_len);
+            setops::merge(
+                IntoIter { entries: left },
+                IntoIter { entries: right },
+                op,
+                &self.cmp,
+            )
+        };
+        Self {
+            root,
+            len,
+            cmp: self.cmp,
+            remove_hook: None,
+        }
+    }
+
+    // The entries of this map whose keys are in `other` (if `shared`) or are
+    // not in it, taken from borrowed maps.  A much smaller side has its keys
+    // looked up in the larger one and maps of similar size are walked side
+    // by side, so neither is copied whole; the exception is taking a few
+    // keys out of a large map, where the copy is most of the result anyway.
+    //
+    pub(crate) fn filter_shared(&self, other: &Self, shared: bool) -> Self
+    where
+        V: Clone,
+        C: Clone,
+    {
+        let looks_up = |small: &Self, big: &Self| small.len.saturating_mul(big.height()) < big.len;
+        let cloned = |(key, value): (&K, &V)| (key.clone(), value.clone());
+        if looks_up(self, other) {
+            let entries = self
+                .iter()
+                .filter(|(key, _)| other.contains_key(key) == shared);
+            Self::build(entries.map(cloned), self.cmp.clone())
+        } else if looks_up(other, self) && shared {
+            let entries = other.iter().filter_map(|(key, _)| self.get_key_value(key));
+            Self::build(entries.map(cloned), self.cmp.clone())
+        } else if looks_up(other, self) {
+            let mut result = self.clone();
+            result.remove_hook = None;
+            for (key, _) in other.iter() {
+                result.remove(key);
+            }
+            result
+        } else {
+            let mut rights = other.iter().peekable();
+            let entries = self.iter().filter(move |(key, _)| {
+                while rights
+                    .next_if(|(right, _)| self.cmp.compare(right, key).is_lt())
+                    .is_some()
+                {}
+                let found = rights
+                    .peek()
+                    .is_some_and(|(right, _)| self.cmp.compare(right, key).is_eq());
+                found == shared
+            });
+            Self::build(entries.map(cloned), self.cmp.clone())
+        }
+    }
+
+    // Whether no key is in both maps.  A much smaller map has its keys
+    // looked up in the larger one; maps of similar size are walked side by
+    // side in linear time.
+    //
+    pub(crate) fn is_disjoint(&self, other: &Self) -> bool {
+        let (small, big) = if self.len <= other.len {
+            (self, other)
+        } else {
+            (other, self)
+        };
+        if small.len.saturating_mul(big.height()) < big.len {
+            return small.iter().all(|(key, _)| !big.contains_key(key));
+        }
+        let (mut lefts, mut rights) = (self.iter(), other.iter());
+        let (mut left, mut right) = (lefts.next(), rights.next());
+        while let (Some((left_key, _)), Some((right_key, _))) = (left, right) {
+            match self.cmp.compare(left_key, right_key) {
+                Ordering::Less => left = lefts.next(),
+                Ordering::Greater => right = rights.next(),
+                Ordering::Equal => return false,
+            }
+        }
+        true
+    }
+
+    // Whether every key in this map is also in `other`, found the same way
+    // as `is_disjoint`.
+    //
+    pub(crate) fn is_subset(&self, other: &Self) -> bool {
+        if self.len > other.len {
+            return false;
+        }
+        if self.len.saturating_mul(other.height()) < other.len {
+            return self.iter().all(|(key, _)| other.contains_key(key));
+        }
+        let mut rights = other.iter();
+        self.iter().all(|(key, _)| {
+            rights
+                .find(|(right_key, _)| !self.cmp.compare(right_key, key).is_lt())
+                .is_some_and(|(right_key, _)| self.cmp.compare(right_key, key).is_eq())
+        })
+    }
+
+    fn remove_target(&mut self, target: Target<K>) -> Option<(K, V)> {
+        use RemoveResult::{Drained, NotFound, Ok, Orphaned};
+        let (event, removed) = match self.root.remove(target, &self.cmp) {
+            NotFound => (RemoveEvent::NotFound, None),
+            Ok(val) => (RemoveEvent::Ok, Some(val)),
+            Drained(val) => {
+                *self.root = Nil;
+                debug_assert_eq!(self.len, 1);
+                (RemoveEvent::Drained, Some(val))
+            }
+            Orphaned(val, new_root) => {
+                self.root = new_root;
+                (RemoveEvent::Orphaned, Some(val))
+            }
+        };
+        if removed.is_some() {
+            self.len -= 1;
+        }
+        if let Some(hook) = self.remove_hook {
+            hook(event);
+        }
+        removed
+    }
+
+    /// Installs a diagnostic hook that is told how each later removal was
+    /// resolved at the root; pass `None` to uninstall it.
+    ///
+    /// The hook belongs to this map: a clone keeps it, but maps derived from
+    /// this one (by `split_off` or a set operation) start without one.
+    pub fn set_remove_hook(&mut self, hook: Option<fn(RemoveEvent)>) {
+        self.remove_hook = hook;
+    }
+
+    /// Returns a reference to the value stored under `key`.
+    pub fn get(&self, key: &K) -> Option<&V> {
+        self.root.find(key, &self.cmp).map(|(_, value)| value)
+    }
+
+    /// Returns a mutable reference to the value stored under `key`.
+    pub fn get_mut(&mut self, key: &K) -> Option<&mut V> {
+        self.root.find_mut(key, &self.cmp).map(|(_, value)| value)
+    }
+
+    /// Returns the stored key and value matching `key`.
+    pub fn get_key_value(&self, key: &K) -> Option<(&K, &V)> {
+        self.root
+            .find(key, &self.cmp)
+            .map(|(key, value)| (key, value))
+    }
+
+    /// Returns the entry with the least key.
+    pub fn first(&self) -> Option<(&K, &V)> {
+        self.root.first().map(|(key, value)| (key, value))
+    }
+
+    /// Returns the entry with the greatest key.
+    pub fn last(&self) -> Option<(&K, &V)> {
+        self.root.last().map(|(key, value)| (key, value))
+    }
+
+    /// Returns the entry with the greatest key less than or equal to `key`.
+    pub fn floor(&self, key: &K) -> Option<(&K, &V)> {
+        self.root
+            .last_before(key, true, &self.cmp)
+            .map(|(key, value)| (key, value))
+    }
+
+    /// Returns the entry with the least key greater than or equal to `key`.
+    pub fn ceiling(&self, key: &K) -> Option<(&K, &V)> {
+        self.root
+            .first_after(key, true, &self.cmp)
+            .map(|(key, value)| (key, value))
+    }
+
+    /// Returns the entry with the greatest key strictly less than `key`.
+    pub fn predecessor(&self, key: &K) -> Option<(&K, &V)> {
+        self.root
+            .last_before(key, false, &self.cmp)
+            .map(|(key, value)| (key, value))
+    }
+
+    /// Returns the entry with the least key strictly greater than `key`.
+    pub fn successor(&self, key: &K) -> Option<(&K, &V)> {
+        self.root
+            .first_after(key, false, &self.cmp)
+            .map(|(key, value)| (key, value))
+    }
+
+    /// Returns true if the map holds an entry for `key`.
+    pub fn contains_key(&self, key: &K) -> bool {
+        self.root.find(key, &self.cmp).is_some()
+    }
+
+    /// Returns the number of entries whose keys are less than `key`, which
+    /// is the position `key` holds or would hold in key order.
+    pub fn rank(&self, key: &K) -> usize {
+        self.root.count_while(|k| self.cmp.compare(k, key).is_lt())
+    }
+
+    /// Returns the entry at position `index` in key order, counting from
+    /// zero.
+    pub fn select(&self, index: usize) -> Option<(&K, &V)> {
+        self.root.select(index).map(|(key, value)| (key, value))
+    }
+
+    /// Returns the number of entries whose keys fall within `range`.
+    ///
+    /// Panics if the start of the range is greater than its end, or if the
+    /// two are equal and both excluded.
+    pub fn count_range<R: RangeBounds<K>>(&self, range: R) -> usize {
+        let (lower, upper) = (range.start_bound(), range.end_bound());
+        check_range(lower, upper, &self.cmp);
+        // The number of entries before `key`, or up to and including it.
+        let count = |key: &K, through: bool| {
+            self.root.count_while(|k| {
+                let order = self.cmp.compare(k, key);
+                order.is_lt() || (through && order.is_eq())
+            })
+        };
+        let below = match lower {
+            Unbounded => 0,
+            Included(key) => count(key, false),
+            Excluded(key) => count(key, true),
+        };
+        let through = match upper {
+            Unbounded => self.len,
+            Included(key) => count(key, true),
+            Excluded(key) => count(key, false),
+        };
+        through - below
+    }
+
+    /// Returns the summary of the keys within `range`.
+    ///
+    /// Every inner node caches the summary of its subtree, so this combines
+    /// O(log n) cached summaries rather than visiting each key.
+    ///
+    /// Panics if the start of the range is greater than its end, or if the
+    /// two are equal and both excluded.
+    pub fn aggregate<R: RangeBounds<K>>(&self, range: R) -> S {
+        let (lower, upper) = (range.start_bound(), range.end_bound());
+        check_range(lower, upper, &self.cmp);
+        self.root.aggregate(None, None, lower, upper, &self.cmp)
+    }
+
+    /// Returns the number of entries in the map.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Returns true if the map holds no entries.
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Returns the number of levels in the tree; 0 when it is empty.
+    pub fn height(&self) -> usize {
+        self.root.height()
+    }
+
+    // The root of the tree, for searches that steer by cached summaries.
+    //
+    pub(crate) fn root(&self) -> &Node<K, V, S> {
+        &self.root
+    }
+
+    /// Returns an iterator over the entries of the map in key order.
+    pub fn iter(&self) -> Iter<'_, K, V, S> {
+        Iter {
+            entries: Entries::new(&self.root, self.len),
+        }
+    }
+
+    /// Returns a cursor positioned on the first entry.
+    pub fn cursor(&self) -> Cursor<'_, K, V, C, S> {
+        Cursor::new(self)
+    }
+
+    /// Returns a cursor that can modify the map, positioned on the first
+    /// entry.
+    pub fn cursor_mut(&mut self) -> CursorMut<'_, K, V, C, S> {
+        CursorMut::new(self)
+    }
+
+    /// Returns an iterator over the entries whose keys fall within `range`,
+    /// in key order.
+    ///
+    /// Panics if the start of the range is greater than its end, or if the
+    /// two are equal and both excluded.
+    pub fn range<R: RangeBounds<K>>(&self, range: R) -> Range<'_, K, V, S> {
+        Range {
+            entries: RangeEntries::new(
+                &self.root,
+                range.start_bound(),
+                range.end_bound(),
+                &self.cmp,
+            ),
+        }
+    }
+
+    // Asserts the structural invariants of the tree and that `len` matches
+    // the number of entries.
+    //
+    #[cfg(test)]
+    pub(crate) fn check(&self) {
+        self.root.check(&self.cmp);
+        assert_eq!(self.iter().count(), self.len);
+        assert_eq!(self.root.size(), self.len);
+    }
+}
+
+impl<K: Clone, V, C: Compare<K> + Default, S: Summary<K>> Default for TreeMap<K, V, C, S> {
+    fn default() -> Self {
+        Self::augmented(C::default())
+    }
+}
+
+// Sorts `entries` by key and drops all but the last of each run of equal keys,
+// matching what inserting them one at a time would leave behind.
+//
+fn sort_dedup<K, V, C: Compare<K>>(mut entries: Vec<(K, V)>, cmp: &C) -> Vec<(K, V)> {
+    entries.sort_by(|a, b| cmp.compare(&a.0, &b.0));
+    let mut sorted: Vec<(K, V)> = Vec::with_capacity(entries.len());
+    for val in entries {
+        match sorted.last_mut() {
+            Some(last) if cmp.compare(&last.0, &val.0).is_eq() => last.1 = val.1,
+            _ => sorted.push(val),
+        }
+    }
+    sorted
+}
+
+impl<K: Clone, V, C: Compare<K> + Default, S: Summary<K>> FromIterator<(K, V)>
+    for TreeMap<K, V, C, S>
+{
+    fn from_iter<I: IntoIterator<Item = (K, V)>>(iter: I) -> Self {
+        let cmp = C::default();
+        let sorted = sort_dedup(iter.into_iter().collect(), &cmp);
+        Self::build(sorted, cmp)
+    }
+}
+
+impl<K: Clone, V, C: Compare<K>, S: Summary<K>> Extend<(K, V)> for TreeMap<K, V, C, S> {
+    // Small batches are inserted one at a time; once the batch is at least as
+    // large as the map, merging both into a freshly built tree is cheaper.
+    //
+    fn extend<I: IntoIterator<Item = (K, V)>>(&mut self, iter: I) {
+        let sorted = sort_dedup(iter.into_iter().collect(), &self.cmp);
+        if sorted.len() < self.len {
+            for (key, value) in sorted {
+                self.insert(key, value);
+            }
+            return;
+        }
+        let root = std::mem::replace(&mut self.root, Box::new(Nil));
+        let old = IntoIter {
+            entries: IntoEntries::new(root, self.len),
+        };
+        let merged = Merge {
+            old: old.peekable(),
+            new: sorted.into_iter().peekable(),
+            cmp: &self.cmp,
+        };
+        let (root, len) = build::from_sorted(merged, &self.cmp);
+        self.root = root;
+        self.len = len;
+    }
+}
+
+// Merges the entries of a map with a sorted batch of new ones; on equal keys
+// the old key is kept with the new value, as `insert` would.
+//
+struct Merge<'a, K, V, S, C, I: Iterator<Item = (K, V)>> {
+    old: std::iter::Peekable<IntoIter<K, V, S>>,
+    new: std::iter::Peekable<I>,
+    cmp: &'a C,
+}
+
+impl<K, V, S, C: Compare<K>, I: Iterator<Item = (K, V)>> Iterator for Merge<'_, K, V, S, C, I> {
+    type Item = (K, V);
+
+    fn next(&mut self) -> Option<(K, V)> {
+        let order = match (self.old.peek(), self.new.peek()) {
+            (Some(old), Some(new)) => self.cmp.compare(&old.0, &new.0),
+            (Some(_), None) => Ordering::Less,
+            (None, _) => Ordering::Greater,
+        };
+        match order {
+            Ordering::Less => self.old.next(),
+            Ordering::Greater => self.new.next(),
+            Ordering::Equal => {
+                let (key, _) = self.old.next()?;
+                let (_, value) = self.new.next()?;
+                Some((key, value))
+            }
+        }
+    }
+}
+
+impl<K, V, C, S> IntoIterator for TreeMap<K, V, C, S> {
+    type Item = (K, V);
+    type IntoIter = IntoIter<K, V, S>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        IntoIter {
+            entries: IntoEntries::new(self.root, self.len),
+        }
+    }
+}
+
+impl<'a, K: Clone, V, C: Compare<K>, S: Summary<K>> IntoIterator for &'a TreeMap<K, V, C, S> {
+    type Item = (&'a K, &'a V);
+    type IntoIter = Iter<'a, K, V, S>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+/// An iterator over the entries of a `TreeMap`, in key order.
+pub struct Iter<'a, K, V, S = ()> {
+    entries: Entries<'a, K, V, S>,
+}
+
+impl<'a, K, V, S> Iterator for Iter<'a, K, V, S> {
+    type Item = (&'a K, &'a V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.entries.next_front().map(|(key, value)| (key, value))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.entries.len(), Some(self.entries.len()))
+    }
+}
+
+impl<K, V, S> DoubleEndedIterator for Iter<'_, K, V, S> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        self.entries.next_back().map(|(key, value)| (key, value))
+    }
+}
+
+impl<K, V, S> ExactSizeIterator for Iter<'_, K, V, S> {}
+
+impl<K, V, S> FusedIterator for Iter<'_, K, V, S> {}
+
+/// An iterator over a sub-range of the entries of a `TreeMap`, in key order.
+pub struct Range<'a, K, V, S = ()> {
+    entries: RangeEntries<'a, K, V, S>,
+}
+
+impl<'a, K, V, S> Iterator for Range<'a, K, V, S> {
+    type Item = (&'a K, &'a V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.entries.next_front().map(|(key, value)| (key, value))
+    }
+}
+
+impl<K, V, S> DoubleEndedIterator for Range<'_, K, V, S> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        self.entries.next_back().map(|(key, value)| (key, value))
+    }
+}
+
+impl<K, V, S> FusedIterator for Range<'_, K, V, S> {}
+
+/// An owning iterator over the entries of a `TreeMap`, in key order.
+pub struct IntoIter<K, V, S = ()> {
+    entries: IntoEntries<K, V, S>,
+}
+
+impl<K, V, S> Iterator for IntoIter<K, V, S> {
+    type Item = (K, V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.entries.next_front()
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.entries.len(), Some(self.entries.len()))
+    }
+}
+
+impl<K, V, S> DoubleEndedIterator for IntoIter<K, V, S> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        self.entries.next_back()
+    }
+}
+
+impl<K, V, S> ExactSizeIterator for IntoIter<K, V, S> {}
+
+impl<K, V, S> FusedIterator for IntoIter<K, V, S> {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn insert_get_test() {
+        let mut m = TreeMap::new();
+
+        assert_eq!(m.get(&1), None);
+
+        for k in 0..10000 {
+            assert_eq!(m.insert(k, k * 2), None);
+        }
+
+        for k in 0..10000 {
+            assert_eq!(m.get(&k), Some(&(k * 2)));
+        }
+
+        for k in 0..10000 {
+            assert_eq!(m.insert(k, k * 3), Some(k * 2));
+        }
+
+        for k in 0..10000 {
+            *m.get_mut(&k).unwrap() += 1;
+        }
+
+        for k in 0..10000 {
+            assert_eq!(m.get_key_value(&k), Some((&k, &(k * 3 + 1))));
+        }
+
+        assert!(m.get_mut(&10000).is_none());
+    }
+
+    #[test]
+    fn iter_test() {
+        let mut m = TreeMap::new();
+
+        assert_eq!(m.iter().next(), None);
+
+        for k in (0..1000).rev() {
+            m.insert(k, -k);
+        }
+
+        let mut iter = m.iter();
+        assert_eq!(iter.len(), 1000);
+        for k in 0..500 {
+            assert_eq!(iter.next(), Some((&k, &-k)));
+            assert_eq!(iter.next_back(), Some((&(999 - k), &(k - 999))));
+            assert_eq!(iter.len(), 998 - 2 * k as usize);
+        }
+        assert_eq!(iter.next(), None);
+        assert_eq!(iter.next_back(), None);
+
+        assert!((&m).into_iter().map(|(k, _)| *k).eq(0..1000));
+    }
+
+    #[test]
+    fn range_test() {
+        use std::ops::Bound::{Excluded, Included, Unbounded};
+
+        let mut m = TreeMap::new();
+
+        for k in 0..100 {
+            m.insert(k * 2, k);
+        }
+
+        for a in -2..202 {
+            for b in a..202 {
+                let expected = (a..b).filter(|k| k % 2 == 0 && (0..200).contains(k));
+                assert!(m.range(a..b).map(|(k, _)| *k).eq(expected.clone()));
+                assert!(m.range(a..b).rev().map(|(k, _)| *k).eq(expected.rev()));
+
+                let expected = (a..=b).filter(|k| k % 2 == 0 && (0..200).contains(k));
+                let mut range = m.range(a..=b);
+                let mut expected = expected.collect::<Vec<_>>().into_iter();
+                loop {
+                    let k = range.next().map(|(k, _)| *k);
+                    assert_eq!(k, expected.next());
+                    let k = range.next_back().map(|(k, _)| *k);
+                    assert_eq!(k, expected.next_back());
+                    if k.is_none() {
+                        break;
+                    }
+                }
+            }
+        }
+
+        assert!(m.range(..).map(|(k, _)| *k).eq((0..200).step_by(2)));
+        assert!(m.range(..=10).map(|(k, _)| *k).eq((0..=10).step_by(2)));
+        assert!(m
+            .range((Excluded(10), Unbounded))
+            .map(|(k, _)| *k)
+            .eq((12..200).step_by(2)));
+        assert!(m
+            .range((Excluded(10), Included(14)))
+            .map(|(k, _)| *k)
+            .eq(vec![12, 14]));
+        assert_eq!(m.range((Excluded(10), Excluded(12))).next(), None);
+        assert_eq!(TreeMap::<i32, ()>::new().range(..).next(), None);
+    }
+
+    #[test]
+    #[should_panic]
+    fn range_backwards_test() {
+        use std::ops::Bound::{Excluded, Included};
+
+        let m: TreeMap<i32, ()> = TreeMap::new();
+        m.range((Included(5), Excluded(3)));
+    }
+
+    #[test]
+    fn into_iter_test() {
+        let mut m = TreeMap::new();
+
+        for k in 0..1000 {
+            m.insert(k, k.to_string());
+        }
+
+        let mut iter = m.clone().into_iter();
+        assert_eq!(iter.len(), 1000);
+        assert_eq!(iter.next(), Some((0, "0".to_string())));
+        assert_eq!(iter.next_back(), Some((999, "999".to_string())));
+        assert_eq!(iter.len(), 998);
+        drop(iter);
+
+        assert!(m.into_iter().eq((0..1000).map(|k| (k, k.to_string()))));
+    }
+
+    #[test]
+    fn entry_test() {
+        let mut m = TreeMap::new();
+
+        for i in 0..20000 {
+            let k = (i * 7919) % 10000;
+            *m.entry(k).or_insert(0) += 1;
+            m.entry(k).and_modify(|v| *v += 10).or_insert(100);
+        }
+
+        assert_eq!(m.len(), 10000);
+
+        for k in 0..10000 {
+            assert_eq!(m.get(&k), Some(&22));
+        }
+
+        match m.entry(5) {
+            Entry::Occupied(mut entry) => {
+                assert_eq!(entry.key(), &5);
+                assert_eq!(entry.insert(7), 22);
+                assert_eq!(entry.get(), &7);
+                assert_eq!(entry.remove_entry(), (5, 7));
+            }
+            Entry::Vacant(_) => panic!("5 should be present"),
+        }
+
+        match m.entry(5) {
+            Entry::Occupied(_) => panic!("5 should be gone"),
+            Entry::Vacant(entry) => {
+                assert_eq!(entry.key(), &5);
+                assert_eq!(entry.into_key(), 5);
+            }
+        }
+
+        assert_eq!(m.len(), 9999);
+        assert_eq!(*m.entry(10000).or_insert_with(|| 3), 3);
+        assert_eq!(*m.entry(-1).or_default(), 0);
+        assert!(m.iter().map(|(k, _)| *k).eq((-1..5).chain(6..10001)));
+    }
+
+    #[test]
+    fn entry_remove_cost_test() {
+        use std::cell::Cell;
+
+        let compares = Cell::new(0);
+        let mut m = TreeMap::with_comparator(|a: &i32, b: &i32| {
+            compares.set(compares.get() + 1);
+            a.cmp(b)
+        });
+        for k in 0..10_000 {
+            m.insert(k, k);
+        }
+        for k in (0..10_000).step_by(5) {
+            m.remove(&k);
+        }
+
+        // Removing through an entry costs nothing beyond finding it.
+        for k in (1..10_000).step_by(3).filter(|k| k % 5 != 0) {
+            compares.set(0);
+            let entry = match m.entry(k) {
+                Entry::Occupied(entry) => entry,
+                Entry::Vacant(_) => panic!("{} should be present", k),
+            };
+            let found = compares.get();
+            assert_eq!(entry.remove(), k);
+            assert_eq!(compares.get(), found);
+        }
+
+        m.check();
+        assert!(m
+            .iter()
+            .map(|(k, _)| *k)
+            .eq((0..10_000).filter(|k| k % 5 != 0 && k % 3 != 1)));
+    }
+
+    #[test]
+    fn entry_insert_position_test() {
+        // The reference returned from a vacant insert must point at the new
+        // entry wherever the splits moved it.
+        for n in 0..100 {
+            for k in 0..=n {
+                let mut m = TreeMap::new();
+                for j in 0..n {
+                    m.insert(j * 2, j);
+                }
+                *m.entry(k * 2 - 1).or_insert(-1) = -2;
+                assert_eq!(m.get(&(k * 2 - 1)), Some(&-2), "n={}, k={}", n, k);
+                assert_eq!(m.len(), n as usize + 1);
+            }
+        }
+    }
+
+    #[test]
+    fn cursor_test() {
+        let mut m = TreeMap::new();
+
+        assert_eq!(m.cursor().peek(), None);
+
+        for k in 0..1000 {
+            m.insert(k * 2, k);
+        }
+
+        let mut cursor = m.cursor();
+        for k in 0..1000 {
+            assert_eq!(cursor.peek(), Some((&(k * 2), &k)));
+            cursor.move_next();
+        }
+        assert_eq!(cursor.peek(), None);
+        cursor.move_prev();
+        assert_eq!(cursor.peek(), Some((&1998, &999)));
+        cursor.move_next();
+        cursor.move_next();
+        assert_eq!(cursor.peek(), Some((&0, &0)));
+        cursor.move_prev();
+        assert_eq!(cursor.peek(), None);
+
+        for k in -1..2000 {
+            cursor.seek(&k);
+            let expected = (k.max(0) + 1) / 2 * 2;
+            assert_eq!(
+                cursor.peek().map(|(k, _)| *k),
+                Some(expected).filter(|k| *k < 2000)
+            );
+        }
+    }
+
+    #[test]
+    fn cursor_mut_test() {
+        let mut m = TreeMap::new();
+
+        for k in 0..1000 {
+            m.insert(k * 2, k);
+        }
+
+        // Walk everything, dropping multiples of 3 and filling in the odd
+        // numbers between the survivors.
+        let mut cursor = m.cursor_mut();
+        while let Some((k, _)) = cursor.peek() {
+            let k = *k;
+            if k % 3 == 0 {
+                assert_eq!(cursor.remove_current(), Some((k, k / 2)));
+            } else {
+                cursor.insert_after(k + 1, -1);
+                *cursor.peek_mut().unwrap().1 += 1000;
+                cursor.move_next();
+                assert_eq!(cursor.peek(), Some((&(k + 1), &-1)));
+                cursor.move_next();
+            }
+        }
+        cursor.insert_before(5000, 0);
+        cursor.insert_after(-5, 0);
+        assert_eq!(cursor.peek(), None);
+
+        let expected = (0..2000)
+            .filter(|k| k % 2 == 0 && k % 3 != 0)
+            .flat_map(|k| vec![(k, k / 2 + 1000), (k + 1, -1)]);
+        let expected: Vec<_> = std::iter::once((-5, 0))
+            .chain(expected)
+            .chain(std::iter::once((5000, 0)))
+            .collect();
+        assert!(m.iter().map(|(k, v)| (*k, *v)).eq(expected.iter().copied()));
+        assert_eq!(m.len(), expected.len());
+    }
+
+    #[test]
+    fn cursor_mut_cost_test() {
+        use std::cell::Cell;
+
+        let compares = Cell::new(0);
+        let mut m = TreeMap::with_comparator(|a: &i32, b: &i32| {
+            compares.set(compares.get() + 1);
+            a.cmp(b)
+        });
+        for k in 0..100_000 {
+            m.insert(k, ());
+        }
+        // Leave plenty of stale separators behind.
+        for k in (0..100_000).step_by(7) {
+            m.remove(&k);
+        }
+
+        let mut cursor = m.cursor_mut();
+        cursor.seek(&50_000);
+        compares.set(0);
+        for _ in 0..1000 {
+            assert!(cursor.remove_current().is_some());
+        }
+        // Removal follows the cursor's path; no key is compared.
+        assert_eq!(compares.get(), 0);
+        let resume = *cursor.peek().unwrap().0;
+
+        // Each insertion checks its two neighbours and at most one
+        // separator, then steps over the new entry.
+        cursor.seek(&0);
+        compares.set(0);
+        let mut inserted = 0;
+        while let Some((&k, _)) = cursor.peek() {
+            if k % 7 == 6 && k < 60_000 {
+                cursor.insert_after(k + 1, ());
+                cursor.move_next();
+                inserted += 1;
+            }
+            cursor.move_next();
+        }
+        assert!(compares.get() <= 3 * inserted);
+
+        m.check();
+        let expected: Vec<i32> = (0..100_000)
+            .filter(|&k| k % 7 != 0 || (0 < k && k < 60_000))
+            .filter(|&k| !(50_000..resume).contains(&k))
+            .collect();
+        assert!(m.iter().map(|(k, _)| *k).eq(expected));
+    }
+
+    #[test]
+    #[should_panic]
+    fn cursor_mut_unordered_insert_test() {
+        let mut m = TreeMap::new();
+        m.insert(1, ());
+        m.insert(3, ());
+        m.cursor_mut().insert_after(5, ());
+    }
+
+    #[test]
+    fn from_iter_test() {
+        let m: TreeMap<i32, i32> = vec![(3, 0), (1, 0), (3, 1), (2, 0)].into_iter().collect();
+        assert!(m.iter().eq(vec![(&1, &0), (&2, &0), (&3, &1)]));
+
+        let mut m = TreeMap::from_sorted((0..100).map(|k| (k, k)));
+        m.extend((50..300).map(|k| (k, -k)));
+        assert_eq!(m.len(), 300);
+        assert!(m
+            .iter()
+            .map(|(k, v)| (*k, *v))
+            .eq((0..50).map(|k| (k, k)).chain((50..300).map(|k| (k, -k)))));
+        m.check();
+
+        m.extend(vec![(1, 1000), (1000, 0)]);
+        assert_eq!(m.get(&1), Some(&1000));
+        assert_eq!(m.len(), 301);
+    }
+
+    #[test]
+    fn split_off_test() {
+        let mut m = TreeMap::from_sorted((0..1000).map(|k| (k, k * 2)));
+        let mut upper = m.split_off(&600);
+        let mut middle = m.split_off(&250);
+        m.check();
+        middle.check();
+        upper.check();
+        assert_eq!((m.len(), middle.len(), upper.len()), (250, 350, 400));
+        assert_eq!(middle.first(), Some((&250, &500)));
+        assert_eq!(upper.last(), Some((&999, &1998)));
+
+        middle.append(&mut upper);
+        m.append(&mut middle);
+        m.check();
+        assert!(m
+            .iter()
+            .map(|(k, v)| (*k, *v))
+            .eq((0..1000).map(|k| (k, k * 2))));
+    }
+
+    #[test]
+    fn extract_if_test() {
+        let mut m = TreeMap::from_sorted((0..1000).map(|k| (k, k)));
+        let evens: Vec<_> = m.extract_if(100..200, |k, _| k % 2 == 0).collect();
+        assert!(evens.iter().map(|(k, _)| *k).eq((100..200).step_by(2)));
+        assert_eq!(m.len(), 950);
+        m.check();
+
+        // Dropping the iterator early leaves the rest of the range alone.
+        //
+        let first: Vec<_> = m.extract_if(.., |_, v| *v >= 500).take(3).collect();
+        assert_eq!(first, vec![(500, 500), (501, 501), (502, 502)]);
+        assert_eq!(m.len(), 947);
+        m.check();
+
+        m.retain(|k, v| {
+            *v *= 2;
+            k % 3 != 0
+        });
+        m.check();
+        assert!(m.iter().all(|(k, v)| k % 3 != 0 && *v == k * 2));
+        assert!(m.extract_if(2000.., |_, _| true).next().is_none());
+        m.check();
+    }
+
+    #[test]
+    fn extract_if_panic_test() {
+        use std::panic::{catch_unwind, AssertUnwindSafe};
+
+        let mut m = TreeMap::from_sorted((0..100).map(|k| (k, k)));
+        let result = catch_unwind(AssertUnwindSafe(|| {
+            m.retain(|&k, _| if k == 50 { panic!() } else { k % 10 != 0 })
+        }));
+        assert!(result.is_err());
+        // The entry the predicate panicked on, and the rest of the range, are
+        // kept as they were.
+        m.check();
+        assert_eq!(m.len(), 95);
+        assert_eq!(m.get(&50), Some(&50));
+        assert!(m.get(&60).is_some());
+
+        // A leaked extraction leaves the map empty but consistent.
+        std::mem::forget(m.extract_if(.., |_, _| true));
+        m.check();
+        assert_eq!(m.len(), 0);
+    }
+
+    #[test]
+    fn remove_range_test() {
+        use std::ops::Bound::{Excluded, Included, Unbounded};
+
+        let mut m = TreeMap::from_sorted((0..10000).map(|k| (k, k)));
+        assert_eq!(m.remove_range(1000..9000), 8000);
+        m.check();
+        assert_eq!(m.remove_range((Excluded(500), Included(9500))), 1000);
+        m.check();
+        assert_eq!(m.remove_range(..=10), 11);
+        assert_eq!(m.remove_range(20000..), 0);
+        assert_eq!(m.remove_range(200..200), 0);
+        m.check();
+        assert!(m.iter().map(|(k, _)| *k).eq((11..=500).chain(9501..10000)));
+        assert_eq!(m.remove_range::<(_, _)>((Unbounded, Unbounded::<i32>)), 989);
+        assert!(m.is_empty());
+        m.check();
+    }
+
+    #[test]
+    fn order_statistics_test() {
+        use std::ops::Bound::{Excluded, Unbounded};
+
+        let mut m: TreeMap<i32, i32> = (0..1000).map(|k| (k * 2, k)).collect();
+        assert_eq!(m.rank(&0), 0);
+        assert_eq!(m.rank(&7), 4);
+        assert_eq!(m.rank(&8), 4);
+        assert_eq!(m.rank(&5000), 1000);
+        assert_eq!(m.select(4), Some((&8, &4)));
+        assert_eq!(m.select(1000), None);
+        assert_eq!(m.count_range(10..20), 5);
+        assert_eq!(m.count_range(10..=20), 6);
+        assert_eq!(m.count_range((Excluded(10), Unbounded)), 994);
+        assert_eq!(m.count_range(..), 1000);
+        assert_eq!(m.count_range(3..3), 0);
+
+        for k in (0..2000).step_by(6) {
+            m.remove(&k);
+        }
+        m.insert(1, -1);
+        m.check();
+        for (i, (k, _)) in m.iter().enumerate() {
+            assert_eq!(m.rank(k), i);
+            assert_eq!(m.select(i).map(|(k, _)| *k), Some(*k));
+        }
+    }
+
+    #[test]
+    fn remove_test() {
+        let mut m = TreeMap::new();
+
+        for k in 0..1000 {
+            m.insert(k, format!("v{}", k));
+        }
+
+        assert_eq!(m.len(), 1000);
+
+        for k in (0..1000).rev() {
+            assert_eq!(m.remove(&k), Some((k, format!("v{}", k))));
+            assert_eq!(m.remove(&k), None);
+            assert!(!m.contains_key(&k));
+            assert_eq!(m.len(), k);
+        }
+
+        assert!(m.is_empty());
+        assert_eq!(m.height(), 0);
+    }
+
+    #[test]
+    fn remove_hook_test() {
+        use std::cell::RefCell;
+
+        thread_local! {
+            static EVENTS: RefCell<Vec<RemoveEvent>> = const { RefCell::new(Vec::new()) };
+        }
+
+        fn record(event: RemoveEvent) {
+            EVENTS.with(|events| events.borrow_mut().push(event));
+        }
+
+        let mut m = TreeMap::new();
+        for k in 0..3 {
+            m.insert(k, ());
+        }
+
+        m.set_remove_hook(Some(record));
+        // Maps derived from this one do not report to the hook.
+        let other: TreeMap<i32, ()> = (0..3).map(|k| (k, ())).collect();
+        let mut derived = vec![
+            m.filter_shared(&other, true),
+            m.filter_shared(&other, false),
+            m.clone().combine(other.clone(), SetOp::Union),
+            m.clone().split_off(&1),
+        ];
+        for map in &mut derived {
+            map.remove(&1);
+            map.remove(&7);
+        }
+        m.remove(&7);
+        m.remove(&0);
+        m.remove(&1);
+        m.remove(&2);
+        m.set_remove_hook(None);
+        m.remove(&2);
+
+        EVENTS.with(|events| {
+            assert_eq!(
+                *events.borrow(),
+                vec![
+                    RemoveEvent::NotFound,
+                    RemoveEvent::Orphaned,
+                    RemoveEvent::Ok,
+                    RemoveEvent::Drained
+                ]
+            );
+        });
+    }
+}
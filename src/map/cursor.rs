@@ -0,0 +1,238 @@
+use crate::compare::Compare;
+use crate::summary::Summary;
+
+use super::TreeMap;
+
+/// A cursor over a `TreeMap`, positioned on an entry or on the "ghost"
+/// position that sits between the last entry and the first.
+///
+/// The cursor keeps the path of child indices from the root to its entry, so
+/// stepping to a neighbour never compares keys.
+pub struct Cursor<'a, K, V, C, S> {
+    map: &'a TreeMap<K, V, C, S>,
+    path: Vec<usize>,
+}
+
+/// A cursor over a `TreeMap` that can also insert and remove entries.
+///
+/// Removing or inserting may rebalance the tree; the cursor re-derives its
+/// path afterwards, so it stays valid.
+pub struct CursorMut<'a, K, V, C, S> {
+    map: &'a mut TreeMap<K, V, C, S>,
+    path: Vec<usize>,
+}
+
+// The path to the first entry at or after `key`, or the ghost (an empty path)
+// if there is none.
+//
+fn seek_path<K: Clone, V, C: Compare<K>, S: Summary<K>>(
+    map: &TreeMap<K, V, C, S>,
+    key: &K,
+) -> Vec<usize> {
+    let mut path = Vec::with_capacity(map.height() + 1);
+    map.root.search(key, &map.cmp, &mut path);
+    let slot = *path.last().expect("search records a leaf slot");
+    if slot < map.root.leaf_len_at(&path) {
+        return path;
+    }
+    if slot == 0 {
+        // Empty tree.
+        return Vec::new();
+    }
+    // Past the end of its leaf: step on from the leaf's last entry.
+    *path.last_mut().unwrap() = slot - 1;
+    if map.root.step_path(&mut path, 0, true) {
+        path
+    } else {
+        Vec::new()
+    }
+}
+
+// The insertion slot just after the entry that `path` leads to.
+//
+fn after_entry(path: &[usize]) -> Vec<usize> {
+    let mut path = path.to_vec();
+    *path.last_mut().expect("path leads to an entry") += 1;
+    path
+}
+
+// Moves `path` one entry forward (or back), wrapping through the ghost.
+//
+fn move_path<K: Clone, V, C, S: Summary<K>>(
+    map: &TreeMap<K, V, C, S>,
+    path: &mut Vec<usize>,
+    forward: bool,
+) {
+    if path.is_empty() {
+        map.root.edge_path(path, forward);
+    } else if !map.root.step_path(path, 0, forward) {
+        path.clear();
+    }
+}
+
+impl<'a, K: Clone, V, C: Compare<K>, S: Summary<K>> Cursor<'a, K, V, C, S> {
+    pub(super) fn new(map: &'a TreeMap<K, V, C, S>) -> Self {
+        let mut path = Vec::with_capacity(map.height() + 1);
+        map.root.edge_path(&mut path, true);
+        Self { map, path }
+    }
+
+    /// Returns the entry under the cursor, or `None` at the ghost position.
+    pub fn peek(&self) -> Option<(&'a K, &'a V)> {
+        if self.path.is_empty() {
+            return None;
+        }
+        let (key, value) = self.map.root.entry_at(&self.path);
+        Some((key, value))
+    }
+
+    /// Moves to the next entry; from the last entry this is the ghost, and
+    /// from the ghost it is the first entry.
+    pub fn move_next(&mut self) {
+        move_path(self.map, &mut self.path, true);
+    }
+
+    /// Moves to the previous entry; from the first entry this is the ghost,
+    /// and from the ghost it is the last entry.
+    pub fn move_prev(&mut self) {
+        move_path(self.map, &mut self.path, false);
+    }
+
+    /// Moves to the first entry whose key is at or after `key`, or to the
+    /// ghost if there is none.
+    pub fn seek(&mut self, key: &K) {
+        self.path = seek_path(self.map, key);
+    }
+}
+
+impl<'a, K: Clone, V, C: Compare<K>, S: Summary<K>> CursorMut<'a, K, V, C, S> {
+    pub(super) fn new(map: &'a mut TreeMap<K, V, C, S>) -> Self {
+        let mut path = Vec::with_capacity(map.height() + 1);
+        map.root.edge_path(&mut path, true);
+        Self { map, path }
+    }
+
+    /// Returns the entry under the cursor, or `None` at the ghost position.
+    pub fn peek(&self) -> Option<(&K, &V)> {
+        if self.path.is_empty() {
+            return None;
+        }
+        let (key, value) = self.map.root.entry_at(&self.path);
+        Some((key, value))
+    }
+
+    /// Returns the entry under the cursor with its value mutable.
+    pub fn peek_mut(&mut self) -> Option<(&K, &mut V)> {
+        if self.path.is_empty() {
+            return None;
+        }
+        let (key, value) = self.map.root.entry_at_mut(&self.path);
+        Some((&*key, value))
+    }
+
+    /// Moves to the next entry; from the last entry this is the ghost, and
+    /// from the ghost it is the first entry.
+    pub fn move_next(&mut self) {
+        move_path(self.map, &mut self.path, true);
+    }
+
+    /// Moves to the previous entry; from the first entry this is the ghost,
+    /// and from the ghost it is the last entry.
+    pub fn move_prev(&mut self) {
+        move_path(self.map, &mut self.path, false);
+    }
+
+    /// Moves to the first entry whose key is at or after `key`, or to the
+    /// ghost if there is none.
+    pub fn seek(&mut self, key: &K) {
+        self.path = seek_path(self.map, key);
+    }
+
+    /// Removes the entry under the cursor and moves on to the next one.
+    ///
+    /// Returns `None`, and does nothing, at the ghost position.
+    pub fn remove_current(&mut self) -> Option<(K, V)> {
+        if self.path.is_empty() {
+            return None;
+        }
+        let (removed, next) = self.map.remove_at(&self.path);
+        self.path = next;
+        Some(removed)
+    }
+
+    /// Inserts an entry just before the cursor; the cursor stays where it is.
+    ///
+    /// At the ghost position the entry becomes the new last entry.
+    ///
+    /// Panics unless `key` orders strictly between the previous entry and
+    /// the one under the cursor.
+    pub fn insert_before(&mut self, key: K, value: V) {
+        let new_path = self.insert_between(key, value, false);
+        if !self.path.is_empty() {
+            self.path = new_path;
+            assert!(self.map.root.step_path(&mut self.path, 0, true));
+        }
+    }
+
+    /// Inserts an entry just after the cursor; the cursor stays where it is.
+    ///
+    /// At the ghost position the entry becomes the new first entry.
+    ///
+    /// Panics unless `key` orders strictly between the entry under the
+    /// cursor and the next one.
+    pub fn insert_after(&mut self, key: K, value: V) {
+        let new_path = self.insert_between(key, value, true);
+        if !self.path.is_empty() {
+            self.path = new_path;
+            assert!(self.map.root.step_path(&mut self.path, 0, false));
+        }
+    }
+
+    // Inserts next to the cursor after checking the key against both of its
+    // would-be neighbours; returns the path to the new entry.
+    //
+    fn insert_between(&mut self, key: K, value: V, after: bool) -> Vec<usize> {
+        let mut neighbour = self.path.clone();
+        move_path(self.map, &mut neighbour, after);
+        let (lower, upper) = if after {
+            (&self.path, &neighbour)
+        } else {
+            (&neighbour, &self.path)
+        };
+        let root = &self.map.root;
+        let cmp = &self.map.cmp;
+        assert!(
+            lower.is_empty() || cmp.compare(&root.entry_at(lower).0, &key).is_lt(),
+            "key is not after the previous entry"
+        );
+        assert!(
+            upper.is_empty() || cmp.compare(&key, &root.entry_at(upper).0).is_lt(),
+            "key is not before the next entry"
+        );
+        // The new entry goes at the end of the lower neighbour's leaf or at
+        // the start of the upper one's; where the two sit under different
+        // children, the separator between those children decides which.
+        let path = match (lower.is_empty(), upper.is_empty()) {
+            (true, true) => vec![0],
+            (true, false) => upper.clone(),
+            (false, true) => after_entry(lower),
+            (false, false) => {
+                let fork = lower
+                    .iter()
+                    .zip(upper.iter())
+                    .position(|(a, b)| a != b)
+                    .expect("neighbours have different paths");
+                if fork + 1 == upper.len()
+                    || !cmp
+                        .compare(&key, root.separator_at(&upper[..=fork]))
+                        .is_lt()
+                {
+                    upper.clone()
+                } else {
+                    after_entry(lower)
+                }
+            }
+        };
+        self.map.insert_at(&path, (key, value))
+    }
+}
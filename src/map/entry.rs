@@ -0,0 +1,142 @@
+use crate::compare::Compare;
+use crate::remove::Target;
+use crate::summary::Summary;
+
+use super::TreeMap;
+
+/// A view into a single entry of a `TreeMap`, which may be vacant or
+/// occupied; returned by `TreeMap::entry`.
+pub enum Entry<'a, K, V, C, S> {
+    Occupied(OccupiedEntry<'a, K, V, C, S>),
+    Vacant(VacantEntry<'a, K, V, C, S>),
+}
+
+/// An entry whose key is present in the map.
+///
+/// Holds the path of child indices from the root to the entry, so reaching or
+/// removing it again does not compare keys.
+pub struct OccupiedEntry<'a, K, V, C, S> {
+    map: &'a mut TreeMap<K, V, C, S>,
+    path: Vec<usize>,
+}
+
+/// An entry whose key is absent from the map.
+///
+/// Holds the path of child indices down to where the key belongs, so
+/// inserting it replays that path rather than searching again.
+pub struct VacantEntry<'a, K, V, C, S> {
+    map: &'a mut TreeMap<K, V, C, S>,
+    key: K,
+    path: Vec<usize>,
+}
+
+impl<'a, K: Clone, V, C: Compare<K>, S: Summary<K>> Entry<'a, K, V, C, S> {
+    pub(super) fn new(map: &'a mut TreeMap<K, V, C, S>, key: K) -> Self {
+        let mut path = Vec::with_capacity(map.height() + 1);
+        if map.root.search(&key, &map.cmp, &mut path) {
+            Entry::Occupied(OccupiedEntry { map, path })
+        } else {
+            Entry::Vacant(VacantEntry { map, key, path })
+        }
+    }
+
+    /// Returns the key of this entry.
+    pub fn key(&self) -> &K {
+        match self {
+            Entry::Occupied(entry) => entry.key(),
+            Entry::Vacant(entry) => entry.key(),
+        }
+    }
+
+    /// Inserts `default` if the entry is vacant; returns the entry's value.
+    pub fn or_insert(self, default: V) -> &'a mut V {
+        match self {
+            Entry::Occupied(entry) => entry.into_mut(),
+            Entry::Vacant(entry) => entry.insert(default),
+        }
+    }
+
+    /// Inserts the result of `default` if the entry is vacant; returns the
+    /// entry's value.
+    pub fn or_insert_with<F: FnOnce() -> V>(self, default: F) -> &'a mut V {
+        match self {
+            Entry::Occupied(entry) => entry.into_mut(),
+            Entry::Vacant(entry) => entry.insert(default()),
+        }
+    }
+
+    /// Inserts `V::default()` if the entry is vacant; returns the entry's
+    /// value.
+    pub fn or_default(self) -> &'a mut V
+    where
+        V: Default,
+    {
+        self.or_insert_with(V::default)
+    }
+
+    /// Applies `f` to the value if the entry is occupied.
+    pub fn and_modify<F: FnOnce(&mut V)>(mut self, f: F) -> Self {
+        if let Entry::Occupied(entry) = &mut self {
+            f(entry.get_mut());
+        }
+        self
+    }
+}
+
+impl<'a, K: Clone, V, C: Compare<K>, S: Summary<K>> OccupiedEntry<'a, K, V, C, S> {
+    /// Returns the key stored in the map.
+    pub fn key(&self) -> &K {
+        &self.map.root.entry_at(&self.path).0
+    }
+
+    /// Returns a reference to the value.
+    pub fn get(&self) -> &V {
+        &self.map.root.entry_at(&self.path).1
+    }
+
+    /// Returns a mutable reference to the value.
+    pub fn get_mut(&mut self) -> &mut V {
+        &mut self.map.root.entry_at_mut(&self.path).1
+    }
+
+    /// Converts the entry into a mutable reference to its value that lives
+    /// as long as the borrow of the map.
+    pub fn into_mut(self) -> &'a mut V {
+        &mut self.map.root.entry_at_mut(&self.path).1
+    }
+
+    /// Replaces the value, returning the old one.
+    pub fn insert(&mut self, value: V) -> V {
+        std::mem::replace(self.get_mut(), value)
+    }
+
+    /// Removes the entry from the map, returning its value.
+    pub fn remove(self) -> V {
+        self.remove_entry().1
+    }
+
+    /// Removes the entry from the map, returning the stored key and value.
+    pub fn remove_entry(self) -> (K, V) {
+        self.map
+            .remove_target(Target::Path(&self.path))
+            .expect("occupied entry is in the map")
+    }
+}
+
+impl<'a, K: Clone, V, C: Compare<K>, S: Summary<K>> VacantEntry<'a, K, V, C, S> {
+    /// Returns the key that would be inserted.
+    pub fn key(&self) -> &K {
+        &self.key
+    }
+
+    /// Takes back ownership of the key.
+    pub fn into_key(self) -> K {
+        self.key
+    }
+
+    /// Inserts `value` under the entry's key, returning a reference to it.
+    pub fn insert(self, value: V) -> &'a mut V {
+        let path = self.map.insert_at(&self.path, (self.key, value));
+        &mut self.map.root.entry_at_mut(&path).1
+    }
+}
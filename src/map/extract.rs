@@ -0,0 +1,119 @@
+use std::iter::FusedIterator;
+use std::ops::RangeBounds;
+
+use crate::build;
+use crate::compare::Compare;
+use crate::iter::{check_range, IntoEntries};
+use crate::join::{self, Part};
+use crate::node::Node;
+use crate::summary::Summary;
+
+use super::TreeMap;
+
+/// An iterator that removes and yields the entries of a `TreeMap` within a
+/// range that match a predicate, returned by `TreeMap::extract_if`.
+///
+/// Entries the iterator has not reached when it is dropped stay in the map.
+pub struct ExtractIf<'a, K: Clone, V, C: Compare<K>, S: Summary<K>, F> {
+    extract: Extract<'a, K, V, C, S>,
+    pred: F,
+}
+
+// The predicate-free part of `ExtractIf`, shared with the set's version.
+//
+// The range is split out of the tree up front and taken apart as it is
+// visited; the entries that are kept pile up in order and, once the
+// extraction is dropped, are built into a fresh subtree in one pass and
+// joined back between the two untouched sides.  No entry is ever removed
+// from the tree one at a time.
+//
+// Until then the map is left empty, with `len` to match, so that leaking
+// the extraction loses entries rather than leaving the count wrong; once
+// it is rebuilt, `len` is taken from the cached size of the new root.
+//
+pub(crate) struct Extract<'a, K: Clone, V, C: Compare<K>, S: Summary<K>> {
+    map: &'a mut TreeMap<K, V, C, S>,
+    below: Part<K, V, S>,
+    above: Part<K, V, S>,
+    within: IntoEntries<K, V, S>,
+    kept: Vec<(K, V)>,
+}
+
+impl<'a, K: Clone, V, C: Compare<K>, S: Summary<K>> Extract<'a, K, V, C, S> {
+    pub(crate) fn new<R: RangeBounds<K>>(map: &'a mut TreeMap<K, V, C, S>, range: R) -> Self {
+        let (lower, upper) = (range.start_bound(), range.end_bound());
+        check_range(lower, upper, &map.cmp);
+        let root = std::mem::replace(&mut map.root, Box::new(Node::Nil));
+        map.len = 0;
+        let height = root.height();
+        let [below, within, above] = join::split_range(*root, height, lower, upper, &map.cmp);
+        let size = within.root.size();
+        Self {
+            within: IntoEntries::new(within.root, size),
+            map,
+            below,
+            above,
+            kept: Vec::new(),
+        }
+    }
+
+    pub(crate) fn next_with<F: FnMut(&K, &mut V) -> bool>(
+        &mut self,
+        pred: &mut F,
+    ) -> Option<(K, V)> {
+        while let Some(val) = self.within.next_front() {
+            // Kept before `pred` sees it, so that a panic leaves it in place.
+            self.kept.push(val);
+            let (key, val) = self.kept.last_mut().unwrap();
+            if pred(key, val) {
+                return self.kept.pop();
+            }
+        }
+        None
+    }
+}
+
+impl<K: Clone, V, C: Compare<K>, S: Summary<K>> Drop for Extract<'_, K, V, C, S> {
+    fn drop(&mut self) {
+        while let Some(val) = self.within.next_front() {
+            self.kept.push(val);
+        }
+        let (root, _) = build::from_sorted(self.kept.drain(..), &self.map.cmp);
+        let height = root.height();
+        let below = std::mem::replace(&mut self.below, Part::empty());
+        let above = std::mem::replace(&mut self.above, Part::empty());
+        let joined = join::concat(join::concat(below, Part::new(root, height)), above);
+        self.map.len = joined.root.size();
+        self.map.root = joined.root;
+    }
+}
+
+impl<'a, K: Clone, V, C: Compare<K>, S: Summary<K>, F: FnMut(&K, &mut V) -> bool>
+    ExtractIf<'a, K, V, C, S, F>
+{
+    pub(super) fn new<R: RangeBounds<K>>(
+        map: &'a mut TreeMap<K, V, C, S>,
+        range: R,
+        pred: F,
+    ) -> Self {
+        Self {
+            extract: Extract::new(map, range),
+            pred,
+        }
+    }
+}
+
+impl<K: Clone, V, C: Compare<K>, S: Summary<K>, F: FnMut(&K, &mut V) -> bool> Iterator
+    for ExtractIf<'_, K, V, C, S, F>
+{
+    type Item = (K, V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.extract.next_with(&mut self.pred)
+    }
+}
+
+impl<K: Clone, V, C: Compare<K>, S: Summary<K>, F: FnMut(&K, &mut V) -> bool> FusedIterator
+    for ExtractIf<'_, K, V, C, S, F>
+{
+}
@@ -0,0 +1,122 @@
+use crate::compare::{Compare, Natural};
+use crate::map::TreeMap;
+
+/// An ordered multiset backed by a 2-3 tree.
+///
+/// Each distinct key is stored once, next to the number of times it occurs.
+#[derive(Clone, Debug)]
+pub struct TreeMultiset<K, C = Natural> {
+    counts: TreeMap<K, usize, C>,
+    len: usize,
+}
+
+impl<K: Ord + Clone> TreeMultiset<K> {
+    /// Creates an empty multiset.
+    pub fn new() -> Self {
+        Self::with_comparator(Natural)
+    }
+}
+
+impl<K: Clone, C: Compare<K>> TreeMultiset<K, C> {
+    /// Creates an empty multiset ordered by `cmp`.
+    pub fn with_comparator(cmp: C) -> Self {
+        Self {
+            counts: TreeMap::with_comparator(cmp),
+            len: 0,
+        }
+    }
+
+    /// Adds one occurrence of `key`, returning its new count.
+    pub fn insert(&mut self, key: K) -> usize {
+        self.len += 1;
+        let count = self.counts.entry(key).or_insert(0);
+        *count += 1;
+        *count
+    }
+
+    /// Removes one occurrence of `key`; returns false if it was not present.
+    pub fn remove_one(&mut self, key: &K) -> bool {
+        match self.counts.get_mut(key) {
+            None => return false,
+            Some(count) if *count > 1 => *count -= 1,
+            Some(_) => {
+                self.counts.remove(key);
+            }
+        }
+        self.len -= 1;
+        true
+    }
+
+    /// Removes every occurrence of `key`, returning how many there were.
+    pub fn remove_all(&mut self, key: &K) -> usize {
+        let count = self.counts.remove(key).map_or(0, |(_, count)| count);
+        self.len -= count;
+        count
+    }
+
+    /// Returns the number of occurrences of `key`.
+    pub fn count(&self, key: &K) -> usize {
+        self.counts.get(key).copied().unwrap_or(0)
+    }
+
+    /// Returns true if at least one occurrence of `key` is present.
+    pub fn contains(&self, key: &K) -> bool {
+        self.counts.contains_key(key)
+    }
+
+    /// Returns the total number of occurrences of all keys.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Returns true if the multiset holds no elements.
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+}
+
+impl<K: Clone, C: Compare<K> + Default> Default for TreeMultiset<K, C> {
+    fn default() -> Self {
+        Self::with_comparator(C::default())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn count_test() {
+        let mut s = TreeMultiset::new();
+
+        assert!(s.is_empty());
+
+        for k in 0..1000 {
+            for i in 0..(k % 4) {
+                assert_eq!(s.insert(k), i + 1);
+            }
+        }
+
+        assert_eq!(s.len(), 1500);
+
+        for k in 0..1000 {
+            assert_eq!(s.count(&k), k % 4);
+            assert_eq!(s.contains(&k), k % 4 != 0);
+        }
+
+        for k in 0..1000 {
+            assert_eq!(s.remove_one(&k), k % 4 != 0);
+            assert_eq!(s.count(&k), (k % 4).saturating_sub(1));
+        }
+
+        assert_eq!(s.len(), 750);
+
+        for k in 0..1000 {
+            assert_eq!(s.remove_all(&k), (k % 4).saturating_sub(1));
+            assert!(!s.contains(&k));
+        }
+
+        assert!(s.is_empty());
+        assert_eq!(s.counts.height(), 0);
+    }
+}
@@ -1,23 +1,129 @@
+use crate::summary::Summary;
+
 #[derive(Clone, Debug)]
-pub enum Node {
+pub enum Node<K, V, S> {
     Inner2 {
-        left: Box<Node>,
-        right_min: i32,
-        right: Box<Node>,
+        left: Box<Node<K, V, S>>,
+        right_min: K,
+        right: Box<Node<K, V, S>>,
+        size: usize,
+        summary: S,
     },
     Inner3 {
-        left: Box<Node>,
-        middle_min: i32,
-        middle: Box<Node>,
-        right_min: i32,
-        right: Box<Node>,
+        left: Box<Node<K, V, S>>,
+        middle_min: K,
+        middle: Box<Node<K, V, S>>,
+        right_min: K,
+        right: Box<Node<K, V, S>>,
+        size: usize,
+        summary: S,
     },
     Leaf2 {
-        val: i32,
+        val: (K, V),
     },
     Leaf3 {
-        val1: i32,
-        val2: i32,
+        val1: (K, V),
+        val2: (K, V),
     },
     Nil,
 }
+
+use Node::{Inner2, Inner3, Leaf2, Leaf3, Nil};
+
+impl<K, V, S: Summary<K>> Node<K, V, S> {
+    // Inner nodes cache the number of entries beneath them and the summary of
+    // their keys, so they are only ever built through these two constructors,
+    // which total the children.
+    //
+    pub fn inner2(left: Box<Node<K, V, S>>, right_min: K, right: Box<Node<K, V, S>>) -> Self {
+        let size = left.size() + right.size();
+        let summary = left.summary().combine(&right.summary());
+        Inner2 {
+            left,
+            right_min,
+            right,
+            size,
+            summary,
+        }
+    }
+
+    pub fn inner3(
+        left: Box<Node<K, V, S>>,
+        middle_min: K,
+        middle: Box<Node<K, V, S>>,
+        right_min: K,
+        right: Box<Node<K, V, S>>,
+    ) -> Self {
+        let size = left.size() + middle.size() + right.size();
+        let summary = left
+            .summary()
+            .combine(&middle.summary())
+            .combine(&right.summary());
+        Inner3 {
+            left,
+            middle_min,
+            middle,
+            right_min,
+            right,
+            size,
+            summary,
+        }
+    }
+
+    // The summary of every key in the subtree.
+    //
+    pub fn summary(&self) -> S {
+        match self {
+            Nil => S::empty(),
+            Leaf2 { val } => S::of(&val.0),
+            Leaf3 { val1, val2 } => S::of(&val1.0).combine(&S::of(&val2.0)),
+            Inner2 { summary, .. } | Inner3 { summary, .. } => summary.clone(),
+        }
+    }
+
+    // Re-totals the cached size and summary after one of the node's children
+    // was changed in place.
+    //
+    pub fn update_cache(&mut self) {
+        match self {
+            Inner2 {
+                left,
+                right,
+                size,
+                summary,
+                ..
+            } => {
+                *size = left.size() + right.size();
+                *summary = left.summary().combine(&right.summary());
+            }
+            Inner3 {
+                left,
+                middle,
+                right,
+                size,
+                summary,
+                ..
+            } => {
+                *size = left.size() + middle.size() + right.size();
+                *summary = left
+                    .summary()
+                    .combine(&middle.summary())
+                    .combine(&right.summary());
+            }
+            _ => {}
+        }
+    }
+}
+
+impl<K, V, S> Node<K, V, S> {
+    // The number of entries in the subtree.
+    //
+    pub fn size(&self) -> usize {
+        match self {
+            Nil => 0,
+            Leaf2 { .. } => 1,
+            Leaf3 { .. } => 2,
+            Inner2 { size, .. } | Inner3 { size, .. } => *size,
+        }
+    }
+}
@@ -0,0 +1,385 @@
+use std::iter::{FromIterator, FusedIterator};
+
+use gcollections::ops::{Bounded, Cardinality, IsEmpty};
+use interval::ops::Range;
+use interval::Interval;
+
+use crate::map::{self, TreeMap};
+
+/// A set of integers stored as disjoint closed intervals, backed by a 2-3
+/// tree.
+///
+/// Intervals that overlap or touch are coalesced as they are inserted, so
+/// the stored intervals are always separated by at least one missing point.
+///
+/// ```
+/// use interval::ops::Range;
+/// use interval::Interval;
+/// use two3tree::RangeSet;
+///
+/// let mut free = RangeSet::new();
+/// free.insert_range(Interval::new(0, 99));
+/// free.remove_range(&Interval::new(10, 19));
+/// assert_eq!(free.len(), 2);
+/// assert_eq!(free.measure(), 90);
+/// assert!(!free.contains(15));
+///
+/// let gaps: Vec<_> = free.gaps(&Interval::new(0, 199)).collect();
+/// assert_eq!(gaps, vec![Interval::new(10, 19), Interval::new(100, 199)]);
+/// ```
+#[derive(Clone, Debug)]
+pub struct RangeSet {
+    // Lower bound of each interval to its upper bound.
+    ranges: TreeMap<i32, i32>,
+    measure: u32,
+}
+
+impl RangeSet {
+    /// Creates an empty set.
+    pub fn new() -> Self {
+        Self {
+            ranges: TreeMap::new(),
+            measure: 0,
+        }
+    }
+
+    /// Adds every point of `range` to the set, merging it with any stored
+    /// intervals that it overlaps or touches.
+    pub fn insert_range(&mut self, range: Interval<i32>) {
+        if range.is_empty() {
+            return;
+        }
+        let (mut lower, mut upper) = (range.lower(), range.upper());
+        if let Some((&start, &end)) = self.ranges.floor(&lower) {
+            if end.saturating_add(1) >= lower {
+                lower = start;
+            }
+        }
+        // Everything starting from `lower` up to just past `upper` is
+        // absorbed into the new interval.
+        let reach = upper.saturating_add(1);
+        for (&start, &end) in self.ranges.range(lower..=reach) {
+            upper = upper.max(end);
+            self.measure -= Interval::new(start, end).size();
+        }
+        self.ranges.remove_range(lower..=reach);
+        self.ranges.insert(lower, upper);
+        self.measure += Interval::new(lower, upper).size();
+    }
+
+    /// Removes every point of `range` from the set, splitting any stored
+    /// interval that extends past either end of it.
+    pub fn remove_range(&mut self, range: &Interval<i32>) {
+        if range.is_empty() {
+            return;
+        }
+        let (lower, upper) = (range.lower(), range.upper());
+        let first = match self.ranges.floor(&lower) {
+            Some((&start, &end)) if end >= lower => start,
+            _ => lower,
+        };
+        // Only the first and last intervals touched can stick out of
+        // `range`; their remnants are put back afterwards.
+        let mut remnants = Vec::with_capacity(2);
+        for (&start, &end) in self.ranges.range(first..=upper) {
+            self.measure -= Interval::new(start, end).size();
+            if start < lower {
+                remnants.push((start, lower - 1));
+            }
+            if end > upper {
+                remnants.push((upper + 1, end));
+            }
+        }
+        self.ranges.remove_range(first..=upper);
+        for (start, end) in remnants {
+            self.ranges.insert(start, end);
+            self.measure += Interval::new(start, end).size();
+        }
+    }
+
+    /// Returns true if `point` lies within one of the stored intervals.
+    pub fn contains(&self, point: i32) -> bool {
+        self.ranges
+            .floor(&point)
+            .is_some_and(|(_, &end)| end >= point)
+    }
+
+    /// Returns the stored interval that contains `point`, if any.
+    pub fn range_containing(&self, point: i32) -> Option<Interval<i32>> {
+        match self.ranges.floor(&point) {
+            Some((&start, &end)) if end >= point => Some(Interval::new(start, end)),
+            _ => None,
+        }
+    }
+
+    /// Returns an iterator over the maximal runs of points within `within`
+    /// that are not in the set, in order.
+    pub fn gaps(&self, within: &Interval<i32>) -> Gaps<'_> {
+        if within.is_empty() {
+            return Gaps {
+                next: None,
+                end: 0,
+                ranges: self.ranges.range(..),
+            };
+        }
+        let (lower, upper) = (within.lower(), within.upper());
+        // Start from the interval that may cover `lower`.
+        let first = self.ranges.floor(&lower).map_or(lower, |(&start, _)| start);
+        Gaps {
+            next: Some(lower),
+            end: upper,
+            ranges: self.ranges.range(first..=upper),
+        }
+    }
+
+    /// Returns the number of points in the set.
+    pub fn measure(&self) -> u32 {
+        self.measure
+    }
+
+    /// Returns the number of disjoint intervals the set is stored as.
+    pub fn len(&self) -> usize {
+        self.ranges.len()
+    }
+
+    /// Returns true if the set holds no points.
+    pub fn is_empty(&self) -> bool {
+        self.ranges.is_empty()
+    }
+
+    /// Returns an iterator over the stored intervals, in order.
+    pub fn iter(&self) -> Iter<'_> {
+        Iter {
+            inner: self.ranges.iter(),
+        }
+    }
+
+    // Asserts that the stored intervals are disjoint and non-adjacent, and
+    // that `measure` matches them.
+    //
+    #[cfg(test)]
+    fn check(&self) {
+        self.ranges.check();
+        let mut measure = 0;
+        let mut last: Option<i32> = None;
+        for (&start, &end) in self.ranges.iter() {
+            assert!(start <= end);
+            assert!(last.is_none_or(|last| last.saturating_add(1) < start));
+            measure += Interval::new(start, end).size();
+            last = Some(end);
+        }
+        assert_eq!(measure, self.measure);
+    }
+}
+
+impl Default for RangeSet {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl FromIterator<Interval<i32>> for RangeSet {
+    fn from_iter<I: IntoIterator<Item = Interval<i32>>>(iter: I) -> Self {
+        let mut set = Self::new();
+        set.extend(iter);
+        set
+    }
+}
+
+impl Extend<Interval<i32>> for RangeSet {
+    fn extend<I: IntoIterator<Item = Interval<i32>>>(&mut self, iter: I) {
+        for range in iter {
+            self.insert_range(range);
+        }
+    }
+}
+
+impl<'a> IntoIterator for &'a RangeSet {
+    type Item = Interval<i32>;
+    type IntoIter = Iter<'a>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+/// An iterator over the intervals of a `RangeSet`, in order.
+pub struct Iter<'a> {
+    inner: map::Iter<'a, i32, i32>,
+}
+
+impl Iterator for Iter<'_> {
+    type Item = Interval<i32>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.inner
+            .next()
+            .map(|(&start, &end)| Interval::new(start, end))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.inner.size_hint()
+    }
+}
+
+impl DoubleEndedIterator for Iter<'_> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        self.inner
+            .next_back()
+            .map(|(&start, &end)| Interval::new(start, end))
+    }
+}
+
+impl ExactSizeIterator for Iter<'_> {}
+
+impl FusedIterator for Iter<'_> {}
+
+/// An iterator over the runs of points missing from a `RangeSet` within an
+/// interval, returned by `RangeSet::gaps`.
+pub struct Gaps<'a> {
+    // The first point not yet known to be covered, or `None` once the
+    // whole interval has been accounted for.
+    next: Option<i32>,
+    end: i32,
+    ranges: map::Range<'a, i32, i32>,
+}
+
+impl Iterator for Gaps<'_> {
+    type Item = Interval<i32>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let mut start = self.next?;
+        let end = self.end;
+        for (&lower, &upper) in &mut self.ranges {
+            if lower > start {
+                self.next = upper.checked_add(1).filter(|&next| next <= end);
+                return Some(Interval::new(start, lower - 1));
+            }
+            if upper >= start {
+                match upper.checked_add(1).filter(|&next| next <= end) {
+                    Some(next) => start = next,
+                    None => {
+                        self.next = None;
+                        return None;
+                    }
+                }
+            }
+        }
+        self.next = None;
+        Some(Interval::new(start, end))
+    }
+}
+
+impl FusedIterator for Gaps<'_> {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use gcollections::ops::{Empty, Singleton};
+    use interval::ops::Whole;
+
+    // The runs of set points in `bits`, offset by `base`.
+    fn runs(bits: &[bool], base: i32) -> Vec<Interval<i32>> {
+        let mut runs = Vec::new();
+        let mut start = None;
+        for (i, &bit) in bits.iter().chain([false].iter()).enumerate() {
+            let point = base + i as i32;
+            match (bit, start) {
+                (true, None) => start = Some(point),
+                (false, Some(first)) => {
+                    runs.push(Interval::new(first, point - 1));
+                    start = None;
+                }
+                _ => {}
+            }
+        }
+        runs
+    }
+
+    #[test]
+    fn coalesce_test() {
+        let mut s = RangeSet::new();
+        let mut bits = vec![false; 2000];
+
+        for k in 0..3000 {
+            let lower = (k * 7919) % 1990;
+            let interval = Interval::new(lower, lower + (k * 13) % 9);
+            if k % 3 == 2 {
+                s.remove_range(&interval);
+            } else {
+                s.insert_range(interval);
+            }
+            for bit in &mut bits[lower as usize..=interval.upper() as usize] {
+                *bit = k % 3 != 2;
+            }
+            if k % 100 == 0 {
+                s.check();
+            }
+        }
+        s.check();
+
+        let expected = runs(&bits, 0);
+        assert!(s.iter().eq(expected.iter().copied()));
+        assert_eq!(s.len(), expected.len());
+        assert_eq!(s.measure(), bits.iter().filter(|&&bit| bit).count() as u32);
+
+        for point in -5..2005 {
+            let bit = (0..2000).contains(&point) && bits[point as usize];
+            assert_eq!(s.contains(point), bit, "point={}", point);
+            assert_eq!(s.range_containing(point).is_some(), bit);
+        }
+
+        for lower in (-10..2010).step_by(7) {
+            for len in [0, 1, 5, 40, 500] {
+                let within = Interval::new(lower, lower + len);
+                let missing: Vec<bool> = (lower..=lower + len)
+                    .map(|point| !((0..2000).contains(&point) && bits[point as usize]))
+                    .collect();
+                let gaps: Vec<_> = s.gaps(&within).collect();
+                assert_eq!(gaps, runs(&missing, lower), "within={}", within);
+            }
+        }
+        assert_eq!(s.gaps(&Interval::empty()).next(), None);
+
+        s.remove_range(&Interval::new(-100, 3000));
+        assert!(s.is_empty());
+        assert_eq!(s.measure(), 0);
+        s.check();
+    }
+
+    #[test]
+    fn adjacent_test() {
+        let mut s: RangeSet = (0..100)
+            .map(|k| Interval::new(k * 10, k * 10 + 4))
+            .collect();
+        assert_eq!(s.len(), 100);
+        assert_eq!(s.measure(), 500);
+
+        // Filling the holes one at a time must merge each with both sides.
+        for k in 0..99 {
+            s.insert_range(Interval::new(k * 10 + 5, k * 10 + 9));
+            assert_eq!(s.len(), 99 - k as usize);
+        }
+        assert!(s.iter().eq([Interval::new(0, 994)].iter().copied()));
+        s.check();
+
+        s.remove_range(&Interval::singleton(500));
+        assert!(s.iter().eq([Interval::new(0, 499), Interval::new(501, 994)]
+            .iter()
+            .copied()));
+
+        // The extreme bounds must not overflow.
+        let mut s = RangeSet::new();
+        s.insert_range(Interval::new(i32::MAX - 10, i32::MAX));
+        s.insert_range(Interval::new(i32::MIN + 1, i32::MIN + 10));
+        s.insert_range(Interval::new(i32::MAX - 20, i32::MAX - 11));
+        assert_eq!(s.len(), 2);
+        assert_eq!(s.measure(), 31);
+        assert!(s.contains(i32::MAX));
+        let gaps: Vec<_> = s.gaps(&Interval::whole()).collect();
+        assert_eq!(gaps, vec![Interval::new(i32::MIN + 11, i32::MAX - 21)]);
+        s.remove_range(&Interval::new(i32::MAX, i32::MAX));
+        assert!(!s.contains(i32::MAX));
+        s.check();
+    }
+}
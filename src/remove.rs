@@ -1,8 +1,74 @@
+use crate::compare::Compare;
 use crate::node::Node;
 
-pub enum RemoveResult {
+// Every variant but NotFound carries the entry that was removed.
+//
+pub enum RemoveResult<K, V, S> {
     NotFound,
+    Ok((K, V)),
+    Drained((K, V)),
+    Orphaned((K, V), Box<Node<K, V, S>>),
+}
+
+// Which entry a removal is after: the one matching a key, whichever is
+// currently first or last in the tree, or the one at the end of a path of
+// child indices (as recorded by `Node::search`).
+//
+#[derive(Clone, Copy)]
+pub enum Target<'a, K> {
+    Key(&'a K),
+    First,
+    Last,
+    Path(&'a [usize]),
+}
+
+impl<'a, K> Target<'a, K> {
+    // Whether the entry can only be in the subtree to the left of `sep`,
+    // which sits just left of the child at `index`.
+    //
+    pub fn is_left_of<C: Compare<K>>(&self, sep: &K, index: usize, cmp: &C) -> bool {
+        match self {
+            Target::Key(key) => cmp.compare(key, sep).is_lt(),
+            Target::First => true,
+            Target::Last => false,
+            Target::Path(path) => path[0] < index,
+        }
+    }
+
+    // The target as seen from the child that the descent moves into.
+    //
+    pub fn below(self) -> Target<'a, K> {
+        match self {
+            Target::Path(path) => Target::Path(&path[1..]),
+            target => target,
+        }
+    }
+
+    // Whether `key`, found in a leaf, is the entry; `at_start`/`at_end` say
+    // whether it is the leaf's first/last entry.
+    //
+    pub fn matches<C: Compare<K>>(&self, key: &K, at_start: bool, at_end: bool, cmp: &C) -> bool {
+        match self {
+            Target::Key(target) => cmp.compare(target, key).is_eq(),
+            Target::First => at_start,
+            Target::Last => at_end,
+            Target::Path(path) => (path[0] == 0) == at_start,
+        }
+    }
+}
+
+/// How a removal was resolved at the root of the tree.
+///
+/// Reported to the hook installed with `TreeMap::set_remove_hook` (or
+/// `Tree::set_remove_hook`) after every removal.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RemoveEvent {
+    /// The key was not in the tree.
+    NotFound,
+    /// The key was removed without changing the height of the tree.
     Ok,
+    /// The last element was removed; the tree is now empty.
     Drained,
-    Orphaned(Box<Node>),
+    /// The root was left with a single child, which became the new root.
+    Orphaned,
 }
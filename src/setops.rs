@@ -0,0 +1,230 @@
+use std::cmp::Ordering;
+
+use crate::build;
+use crate::compare::Compare;
+use crate::insert::InsertResult;
+use crate::join::{self, Part};
+use crate::node::Node;
+use crate::remove::{RemoveResult, Target};
+use crate::summary::Summary;
+
+use Node::{Inner2, Inner3, Leaf2, Leaf3, Nil};
+
+// The set operations, described by which entries of the two operands survive.
+// Where a key is present in both, the entry from the left operand is kept.
+//
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SetOp {
+    Union,
+    Intersection,
+    Difference,
+    SymmetricDifference,
+}
+
+use SetOp::{Difference, Intersection, SymmetricDifference, Union};
+
+impl SetOp {
+    fn keeps_left_only(self) -> bool {
+        self != Intersection
+    }
+
+    fn keeps_right_only(self) -> bool {
+        self == Union || self == SymmetricDifference
+    }
+
+    fn keeps_both(self) -> bool {
+        self == Union || self == Intersection
+    }
+
+    // The size of the result, given the sizes of the operands and the number
+    // of keys they share.
+    //
+    pub fn len(self, left_len: usize, right_len: usize, shared: usize) -> usize {
+        match self {
+            Union => left_len + right_len - shared,
+            Intersection => shared,
+            Difference => left_len - shared,
+            SymmetricDifference => left_len + right_len - 2 * shared,
+        }
+    }
+}
+
+// Combines two trees by walking both in order and building the result from
+// scratch, in time linear in their combined size.
+//
+pub fn merge<K, V, S, C, L, R>(left: L, right: R, op: SetOp, cmp: &C) -> (Box<Node<K, V, S>>, usize)
+where
+    K: Clone,
+    S: Summary<K>,
+    C: Compare<K>,
+    L: Iterator<Item = (K, V)>,
+    R: Iterator<Item = (K, V)>,
+{
+    let mut left = left.peekable();
+    let mut right = right.peekable();
+    let entries = std::iter::from_fn(|| loop {
+        let order = match (left.peek(), right.peek()) {
+            (Some(l), Some(r)) => cmp.compare(&l.0, &r.0),
+            (Some(_), None) => Ordering::Less,
+            (None, Some(_)) => Ordering::Greater,
+            (None, None) => return None,
+        };
+        match order {
+            Ordering::Less => {
+                let val = left.next();
+                if op.keeps_left_only() {
+                    return val;
+                }
+            }
+            Ordering::Greater => {
+                let val = right.next();
+                if op.keeps_right_only() {
+                    return val;
+                }
+            }
+            Ordering::Equal => {
+                let val = left.next();
+                right.next();
+                if op.keeps_both() {
+                    return val;
+                }
+            }
+        }
+    });
+    build::from_sorted(entries, cmp)
+}
+
+// Combines a large tree with a much smaller one by recursing over the
+// structure of the small tree: the large tree is split at each separator of a
+// small inner node, the pieces are combined with the matching children, and
+// the results are joined back together at the same separators.  Entries of
+// small leaves are looked up in whatever piece of the large tree is left.
+// This takes O(m log(n/m)) for operands of sizes m and n.
+//
+// `shared` is incremented for every key found in both trees.
+//
+pub fn combine<K: Clone, V, S: Summary<K>, C: Compare<K>>(
+    big: Part<K, V, S>,
+    small: Part<K, V, S>,
+    small_is_left: bool,
+    op: SetOp,
+    shared: &mut usize,
+    cmp: &C,
+) -> Part<K, V, S> {
+    let (keeps_small, keeps_big) = if small_is_left {
+        (op.keeps_left_only(), op.keeps_right_only())
+    } else {
+        (op.keeps_right_only(), op.keeps_left_only())
+    };
+    if big.height == 0 {
+        return if keeps_small { small } else { Part::empty() };
+    }
+    if small.height == 0 {
+        return if keeps_big { big } else { Part::empty() };
+    }
+    let child = small.height - 1;
+    let mut recurse = |big, subtree| {
+        combine(
+            big,
+            Part::new(subtree, child),
+            small_is_left,
+            op,
+            shared,
+            cmp,
+        )
+    };
+    let vals = match *small.root {
+        Nil => unreachable!(),
+
+        Inner2 {
+            left,
+            right_min,
+            right,
+            ..
+        } => {
+            let (lower, upper) = join::split(*big.root, big.height, &right_min, cmp);
+            let left = recurse(lower, left);
+            let right = recurse(upper, right);
+            return join::join(left, right_min, right);
+        }
+
+        Inner3 {
+            left,
+            middle_min,
+            middle,
+            right_min,
+            right,
+            ..
+        } => {
+            let (lower, rest) = join::split(*big.root, big.height, &middle_min, cmp);
+            let (middle_part, upper) = join::split(*rest.root, rest.height, &right_min, cmp);
+            let left = recurse(lower, left);
+            let middle = recurse(middle_part, middle);
+            let right = recurse(upper, right);
+            return join::join(join::join(left, middle_min, middle), right_min, right);
+        }
+
+        Leaf2 { val } => vec![val],
+        Leaf3 { val1, val2 } => vec![val1, val2],
+    };
+
+    let mut big = big;
+    let mut kept = Vec::new();
+    for val in vals {
+        let (found, rest) = remove_part(big, &val.0, cmp);
+        big = rest;
+        let keep = match found {
+            Some(found) => {
+                *shared += 1;
+                match (op.keeps_both(), small_is_left) {
+                    (false, _) => None,
+                    (true, true) => Some(val),
+                    (true, false) => Some(found),
+                }
+            }
+            None if keeps_small => Some(val),
+            None => None,
+        };
+        match keep {
+            Some(val) if keeps_big => big = insert_part(big, val, cmp),
+            Some(val) => kept.push(val),
+            None => {}
+        }
+    }
+    if keeps_big {
+        return big;
+    }
+    let mut kept = kept.into_iter();
+    match (kept.next(), kept.next()) {
+        (None, _) => Part::empty(),
+        (Some(val), None) => Part::new(Box::new(Leaf2 { val }), 1),
+        (Some(val1), Some(val2)) => Part::new(Box::new(Leaf3 { val1, val2 }), 1),
+    }
+}
+
+fn insert_part<K: Clone, V, S: Summary<K>, C: Compare<K>>(
+    mut part: Part<K, V, S>,
+    val: (K, V),
+    cmp: &C,
+) -> Part<K, V, S> {
+    match part.root.insert(val, cmp) {
+        InsertResult::Split(split_min, split) => Part::new(
+            Box::new(Node::inner2(part.root, split_min, split)),
+            part.height + 1,
+        ),
+        _ => Part::new(part.root, part.height.max(1)),
+    }
+}
+
+fn remove_part<K: Clone, V, S: Summary<K>, C: Compare<K>>(
+    mut part: Part<K, V, S>,
+    key: &K,
+    cmp: &C,
+) -> (Option<(K, V)>, Part<K, V, S>) {
+    match part.root.remove(Target::Key(key), cmp) {
+        RemoveResult::NotFound => (None, part),
+        RemoveResult::Ok(val) => (Some(val), part),
+        RemoveResult::Drained(val) => (Some(val), Part::empty()),
+        RemoveResult::Orphaned(val, root) => (Some(val), Part::new(root, part.height - 1)),
+    }
+}
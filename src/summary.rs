@@ -0,0 +1,44 @@
+/// An aggregate over a run of keys, cached in every inner node of a tree so
+/// that the aggregate of any key range can be assembled from O(log n) parts.
+///
+/// `combine` must be associative, with `empty` as its identity; it need not
+/// be commutative, and is always given the earlier run on the left.
+/// Summaries depend on keys only, so values can be changed in place without
+/// invalidating them.
+///
+/// ```
+/// use two3tree::{Natural, Summary, Tree};
+///
+/// #[derive(Clone, Debug, PartialEq)]
+/// struct Sum(i64);
+///
+/// impl Summary<i64> for Sum {
+///     fn empty() -> Self {
+///         Sum(0)
+///     }
+///     fn of(key: &i64) -> Self {
+///         Sum(*key)
+///     }
+///     fn combine(&self, other: &Self) -> Self {
+///         Sum(self.0 + other.0)
+///     }
+/// }
+///
+/// let mut tree: Tree<i64, Natural, Sum> = (1..=100).collect();
+/// assert_eq!(tree.aggregate(..), Sum(5050));
+/// assert_eq!(tree.aggregate(10..20), Sum(145));
+/// tree.remove(&15);
+/// assert_eq!(tree.aggregate(10..20), Sum(130));
+/// ```
+pub trait Summary<K>: Clone {
+    fn empty() -> Self;
+    fn of(key: &K) -> Self;
+    fn combine(&self, other: &Self) -> Self;
+}
+
+/// The summary that records nothing, used by trees that need no aggregates.
+impl<K> Summary<K> for () {
+    fn empty() -> Self {}
+    fn of(_: &K) -> Self {}
+    fn combine(&self, _: &Self) -> Self {}
+}